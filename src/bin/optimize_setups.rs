@@ -0,0 +1,74 @@
+use strategobot::{
+    bot::{AgressoBot, Bot, Budget, PimcBot, RandoBot},
+    game::Ruleset,
+    setup_optimizer::{sort_by_win_rate, BotFactory, SetupOptimizer},
+};
+
+use rand::{RngCore, SeedableRng};
+use rand_xoshiro::SplitMix64;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Evolves setups for a rule variant and prints the best ones as JSON.
+//
+// Usage: optimize_setups [rule variant] [generations] [population size] [games per opponent]
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().collect::<Vec<_>>();
+    let number_arg = |idx: usize, default: usize| {
+        args.get(idx)
+            .map(|arg| arg.parse().expect("Argument should be a number"))
+            .unwrap_or(default)
+    };
+
+    let ruleset = match args.get(1) {
+        Some(name) => Ruleset::by_name(name).expect("Argument should be a known rule variant"),
+        None => Ruleset::barrage(),
+    };
+    let generations = number_arg(2, 10);
+    let population_size = number_arg(3, 16);
+    let games_per_opponent = number_arg(4, 4);
+
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Now is later then epoch")
+        .as_secs();
+    let mut seeder = SplitMix64::seed_from_u64(seed);
+
+    // The setups are played by a quick searching bot, so that they are judged by how they do in
+    // reasonable hands.
+    let budget = Budget {
+        iterations: 8,
+        time: None,
+    };
+    let pimc = move || {
+        Box::new(move |seed| Box::new(PimcBot::new(seed).with_budget(budget)) as Box<dyn Bot>)
+            as BotFactory
+    };
+    let panel: Vec<BotFactory> = vec![
+        Box::new(|seed| Box::new(RandoBot::new(seed))),
+        Box::new(|seed| Box::new(AgressoBot::new(seed))),
+        pimc(),
+    ];
+
+    let mut optimizer = SetupOptimizer::new(ruleset, pimc(), panel, seeder.next_u64())
+        .with_population_size(population_size)
+        .with_games_per_opponent(games_per_opponent);
+
+    let mut population = optimizer.initial_population();
+    for generation in 0..generations {
+        if generation > 0 {
+            population = optimizer.next_generation(population);
+        }
+        optimizer.evaluate(&mut population)?;
+
+        let best = population
+            .iter()
+            .map(|setup| setup.win_rate())
+            .fold(0.0, f32::max);
+        eprintln!("Generation {generation}: best win rate {best:.2}");
+    }
+
+    sort_by_win_rate(&mut population);
+    println!("{}", serde_json::to_string_pretty(&population)?);
+
+    Ok(())
+}
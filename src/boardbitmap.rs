@@ -3,6 +3,9 @@ use std::arch::x86_64::{
     _mm_setzero_si128, _mm_shuffle_epi8, _mm_slli_epi16, _mm_srli_epi16, _mm_test_all_ones,
 };
 
+// The largest number of squares a bitmap can hold.
+pub const MAX_SQUARES: usize = 128;
+
 #[derive(Clone, Copy, Debug)]
 pub struct BoardBitmap {
     data: __m128i,
@@ -16,9 +19,17 @@ impl PartialEq for BoardBitmap {
 }
 
 #[cfg(all(target_arch = "x86_64", target_feature = "sse4.1"))]
-impl BoardBitmap {
-    const OFFSET: usize = 14;
+impl Eq for BoardBitmap {}
+
+#[cfg(all(target_arch = "x86_64", target_feature = "sse4.1"))]
+impl Default for BoardBitmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
+#[cfg(all(target_arch = "x86_64", target_feature = "sse4.1"))]
+impl BoardBitmap {
     pub fn new() -> BoardBitmap {
         unsafe {
             BoardBitmap {
@@ -28,9 +39,7 @@ impl BoardBitmap {
     }
 
     pub fn set(&mut self, idx: usize, val: bool) {
-        debug_assert!(idx < 100);
-
-        let idx = idx + Self::OFFSET;
+        debug_assert!(idx < MAX_SQUARES);
 
         let i = idx / 8;
         let j = idx % 8;
@@ -45,9 +54,7 @@ impl BoardBitmap {
     }
 
     pub fn get(&self, idx: usize) -> bool {
-        debug_assert!(idx < 100);
-
-        let idx = idx + Self::OFFSET;
+        debug_assert!(idx < MAX_SQUARES);
 
         let i = idx / 8;
         let j = idx % 8;
@@ -59,10 +66,18 @@ impl BoardBitmap {
         data[i] & val_mask != 0
     }
 
-    pub fn reversed(&self) -> BoardBitmap {
-        BoardBitmap {
-            data: m128i_reverse_bits(self.data),
-        }
+    // Reverses the order of the first `len` bits, which mirrors a board with `len` squares.
+    pub fn reversed(&self, len: usize) -> BoardBitmap {
+        debug_assert!(len <= MAX_SQUARES);
+
+        // Reversing all 128 bits moves index `i` to `127 - i`, so the bits have to be shifted back
+        // down to end up at `len - 1 - i`.
+        let mut data = m128i_reverse_bits(self.data);
+        let bits = u128::from_le_bytes(m128i_as_slice_u8(&data).try_into().unwrap());
+        m128i_as_mut_slice_u8(&mut data)
+            .copy_from_slice(&(bits >> (MAX_SQUARES - len)).to_le_bytes());
+
+        BoardBitmap { data }
     }
 }
 
@@ -143,6 +158,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_reversed_mirrors_the_used_squares() {
+        for len in [36, 99, 100, 128] {
+            let mut bitmap = BoardBitmap::new();
+            bitmap.set(0, true);
+            bitmap.set(3, true);
+
+            let reversed = bitmap.reversed(len);
+
+            for idx in 0..len {
+                assert_eq!(reversed.get(idx), idx == len - 1 || idx == len - 4);
+            }
+
+            assert_eq!(reversed.reversed(len), bitmap);
+        }
+    }
+
     fn m128i_as_slice_u64(x: &__m128i) -> &[u64] {
         unsafe { std::slice::from_raw_parts(x as *const __m128i as *const u64, 2) }
     }
@@ -1,11 +1,30 @@
+pub mod constraints;
+pub mod evaluation;
+pub mod expectimax;
+pub mod ismcts;
+pub mod pimc;
+pub mod rollout;
+pub mod setup;
+pub mod setup_book;
+
+pub use constraints::*;
+pub use evaluation::*;
+pub use expectimax::*;
+pub use ismcts::*;
+pub use pimc::*;
+pub use rollout::*;
+pub use setup::*;
+pub use setup_book::*;
+
 use crate::{
     game::logic::all_possible_moves,
-    game::{Action, Piece, Position, Rank, State, STARTING_RANKS},
+    game::{Action, Army, Beliefs, Board, Piece, Position, Rank, Ruleset, State},
     reservoir_sample::reservoir_sample,
 };
 
-use rand::{Rng, SeedableRng};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256StarStar;
+use std::time::{Duration, Instant};
 
 pub struct BotOrienter {
     player_id: usize,
@@ -19,67 +38,141 @@ impl BotOrienter {
 }
 
 impl Bot for BotOrienter {
-    fn get_initial_placements(&mut self) -> Vec<(Rank, Position)> {
-        let placements = self.bot.get_initial_placements();
-
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
         if self.player_id == 1 {
-            return placements
+            return self
+                .bot
+                .get_initial_placements(&ruleset.reversed())
                 .into_iter()
-                .map(|(rank, pos)| (rank, pos.reversed()))
+                .map(|(rank, pos)| (rank, pos.reversed(&ruleset.board)))
                 .collect();
         }
 
-        placements
+        self.bot.get_initial_placements(ruleset)
     }
 
-    fn get_action(&mut self, state: State) -> Action {
+    fn get_action(&mut self, state: State, beliefs: &Beliefs) -> Action {
         let state = if self.player_id == 1 {
             state.reversed()
         } else {
             state
         };
 
-        let action = self.bot.get_action(state);
+        let beliefs = if self.player_id == 1 {
+            beliefs.reversed()
+        } else {
+            *beliefs
+        };
+
+        let action = self.bot.get_action(state, &beliefs);
 
         if self.player_id == 1 {
-            action.reversed()
+            action.reversed(&state.ruleset.board)
         } else {
             action
         }
     }
+
+    fn offers_draw(&mut self, state: State) -> bool {
+        let state = if self.player_id == 1 {
+            state.reversed()
+        } else {
+            state
+        };
+
+        self.bot.offers_draw(state)
+    }
+
+    fn accepts_draw(&mut self, state: State) -> bool {
+        let state = if self.player_id == 1 {
+            state.reversed()
+        } else {
+            state
+        };
+
+        self.bot.accepts_draw(state)
+    }
+
+    fn on_game_end(&mut self, result: GameResult) {
+        self.bot.on_game_end(result)
+    }
+}
+
+// How a game ended for a bot.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameResult {
+    Win,
+    Loss,
+    Draw,
 }
 
 pub trait Bot {
-    fn get_initial_placements(&mut self) -> Vec<(Rank, Position)>;
-    fn get_action(&mut self, state: State) -> Action;
+    // The bot always plays as player 0 of the ruleset, so its army is `ruleset.armies[0]`.
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)>;
+    // The beliefs are about the pieces of the opponent, which is player 1 of the state.
+    fn get_action(&mut self, state: State, beliefs: &Beliefs) -> Action;
+
+    // Asked before every move of the bot. A draw is only agreed when the opponent accepts it.
+    fn offers_draw(&mut self, _state: State) -> bool {
+        false
+    }
+
+    fn accepts_draw(&mut self, _state: State) -> bool {
+        false
+    }
+
+    // Called once the game is over, when the result is known.
+    fn on_game_end(&mut self, _result: GameResult) {}
 }
 
-fn random_placement<R: Rng>(rng: &mut R, ranks: &[Rank]) -> Vec<(Rank, Position)> {
-    let all_positions = itertools::iproduct!((0..10), (0..4)).map(|(x, y)| Position { x, y });
+// Limits how long a searching bot thinks about a single action. The search stops as soon as it has
+// done `iterations` iterations, or when the time is up. What an iteration is depends on the bot.
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    pub iterations: usize,
+    pub time: Option<Duration>,
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Budget {
+            iterations: 32,
+            time: Some(Duration::from_millis(500)),
+        }
+    }
+}
+
+impl Budget {
+    pub fn is_spent(&self, iterations: usize, start: Instant) -> bool {
+        iterations >= self.iterations || self.time.is_some_and(|time| start.elapsed() >= time)
+    }
+}
+
+pub fn random_placement<R: Rng>(rng: &mut R, army: &Army, board: &Board) -> Vec<(Rank, Position)> {
+    let all_positions = board.deployment_zone();
+
+    let mut chosen_positions = reservoir_sample(rng, all_positions, army.len());
 
-    let chosen_positions = reservoir_sample(rng, all_positions, ranks.len());
+    // The reservoir keeps positions in iteration order until the source runs out, so when the
+    // army fills the whole deployment zone the positions need to be shuffled separately.
+    chosen_positions.shuffle(rng);
 
     chosen_positions
         .into_iter()
-        .zip(ranks)
-        .map(|(pos, rank)| (*rank, pos))
+        .zip(army.ranks())
+        .map(|(pos, rank)| (rank, pos))
         .collect::<Vec<_>>()
 }
 
 fn random_action<R: Rng>(rng: &mut R, state: State, action_buffer: &mut Vec<Action>) -> Action {
     action_buffer.clear();
 
-    all_possible_moves(
-        &state.pieces[0],
-        &state.bitmaps[0],
-        &state.bitmaps[1],
-        action_buffer,
-    );
+    all_possible_moves(&state, action_buffer);
 
     *pick_randomly(rng, action_buffer)
 }
 
-fn pick_randomly<'a, R: Rng, T>(rng: &mut R, elems: &'a [T]) -> &'a T {
+pub fn pick_randomly<'a, R: Rng, T>(rng: &mut R, elems: &'a [T]) -> &'a T {
     let idx = rng.gen_range(0..elems.len());
     &elems[idx]
 }
@@ -99,11 +192,11 @@ impl RandoBot {
 }
 
 impl Bot for RandoBot {
-    fn get_initial_placements(&mut self) -> Vec<(Rank, Position)> {
-        random_placement(&mut self.rng, &STARTING_RANKS)
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+        random_placement(&mut self.rng, &ruleset.armies[0], &ruleset.board)
     }
 
-    fn get_action(&mut self, state: State) -> Action {
+    fn get_action(&mut self, state: State, _beliefs: &Beliefs) -> Action {
         // There is guaranteed to be at least one possible move, otherwise the game would be over.
         random_action(&mut self.rng, state, &mut self.move_buffer)
     }
@@ -124,29 +217,24 @@ impl AgressoBot {
 }
 
 impl Bot for AgressoBot {
-    fn get_initial_placements(&mut self) -> Vec<(Rank, Position)> {
-        random_placement(&mut self.rng, &STARTING_RANKS)
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+        random_placement(&mut self.rng, &ruleset.armies[0], &ruleset.board)
     }
 
-    fn get_action(&mut self, state: State) -> Action {
+    fn get_action(&mut self, state: State, _beliefs: &Beliefs) -> Action {
         self.action_buffer.clear();
-        all_possible_moves(
-            &state.pieces[0],
-            &state.bitmaps[0],
-            &state.bitmaps[1],
-            &mut self.action_buffer,
-        );
+        all_possible_moves(&state, &mut self.action_buffer);
 
         let action_scores = self.action_buffer.iter().map(|Action { from, to }| {
             let current_score = state.pieces[1]
                 .iter()
-                .map(|Piece { pos, .. }| from.manhattan_distance(pos) as i8)
-                .sum::<i8>();
+                .map(|Piece { pos, .. }| from.manhattan_distance(pos) as i32)
+                .sum::<i32>();
 
             let new_score = state.pieces[1]
                 .iter()
-                .map(|Piece { pos, .. }| to.manhattan_distance(pos) as i8)
-                .sum::<i8>();
+                .map(|Piece { pos, .. }| to.manhattan_distance(pos) as i32)
+                .sum::<i32>();
 
             new_score - current_score
         });
@@ -159,7 +247,7 @@ impl Bot for AgressoBot {
 
         scored_actions.sort_by_key(|&(_, score)| score);
 
-        let best_score: i8 = scored_actions[0].1;
+        let best_score: i32 = scored_actions[0].1;
         let end = scored_actions
             .iter()
             .position(|&(_, score)| score != best_score)
@@ -0,0 +1,533 @@
+use crate::bot::{random_placement, Bot, GameResult};
+use crate::game::{Action, Army, Beliefs, Board, Position, Rank, Ruleset, State, ALL_RANKS};
+use anyhow::Context;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+// How many times the search for a placement starts over from a new random placement, and how many
+// swaps it tries each time, before it gives up.
+const RESTARTS: usize = 8;
+const SWAPS_PER_RESTART: usize = 4000;
+
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum ConstraintError {
+    #[error("Can't understand the constraint \"{text}\"")]
+    InvalidConstraint { text: String },
+    #[error("\"{name}\" is not a rank")]
+    UnknownRank { name: String },
+    #[error("The constraint \"{constraint}\" can't be satisfied: {reason}")]
+    Unsatisfiable { constraint: String, reason: String },
+    #[error("No placement satisfies all constraints at once, after {attempts} attempts")]
+    NoPlacementFound { attempts: usize },
+}
+
+// Rows are counted from the back of the deployment zone and columns from the left, as seen by
+// the player who deploys.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Region {
+    Row(u8),
+    FrontRow,
+    Column(u8),
+    AdjacentTo(Rank),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Quantity {
+    All,
+    AtLeast(usize),
+    AtMost(usize),
+}
+
+// A rule about where the pieces of one rank go, like "Flag on row 0", "a Bomb adjacent to the
+// Flag", "Marshal not in column 0" or "at least 2 Scouts on the front row". Without a quantity
+// the rule holds for every piece of the rank, and "not" counts the pieces outside the region.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Constraint {
+    pub rank: Rank,
+    pub quantity: Quantity,
+    pub region: Region,
+    pub negated: bool,
+}
+
+impl Constraint {
+    fn contains(&self, pos: &Position, placement: &[(Rank, Position)], board: &Board) -> bool {
+        let is_inside = match self.region {
+            Region::Row(row) => pos.y == row,
+            Region::FrontRow => pos.y + 1 == board.deployment_rows,
+            Region::Column(column) => pos.x == column,
+            Region::AdjacentTo(other) => placement
+                .iter()
+                .any(|(rank, other_pos)| *rank == other && pos.manhattan_distance(other_pos) == 1),
+        };
+
+        is_inside != self.negated
+    }
+
+    // How many pieces would have to move for the constraint to hold.
+    fn violations(&self, placement: &[(Rank, Position)], board: &Board) -> usize {
+        let pieces = placement.iter().filter(|(rank, _)| *rank == self.rank);
+        let total = pieces.clone().count();
+        let inside = pieces
+            .filter(|(_, pos)| self.contains(pos, placement, board))
+            .count();
+
+        match self.quantity {
+            Quantity::All => total - inside,
+            Quantity::AtLeast(count) => count.saturating_sub(inside),
+            Quantity::AtMost(count) => inside.saturating_sub(count),
+        }
+    }
+
+    // Catches the constraints that can't hold on their own, whatever the other pieces do.
+    fn check(&self, army: &Army, board: &Board) -> Result<(), ConstraintError> {
+        let unsatisfiable = |reason: String| ConstraintError::Unsatisfiable {
+            constraint: self.to_string(),
+            reason,
+        };
+
+        let count = army.count(self.rank);
+        let needed = match self.quantity {
+            Quantity::All => count,
+            Quantity::AtLeast(needed) if needed > count => {
+                return Err(unsatisfiable(format!("the army has only {count}")));
+            }
+            Quantity::AtLeast(needed) => needed,
+            Quantity::AtMost(_) => 0,
+        };
+
+        match self.region {
+            Region::Row(row) if row >= board.deployment_rows => {
+                return Err(unsatisfiable(format!(
+                    "there are only {} rows to deploy on",
+                    board.deployment_rows
+                )));
+            }
+            Region::Column(column) if column >= board.width => {
+                return Err(unsatisfiable(format!(
+                    "the board has only {} columns",
+                    board.width
+                )));
+            }
+            Region::AdjacentTo(other) if army.count(other) == 0 && needed > 0 => {
+                return Err(unsatisfiable(format!("the army has no {other:?}")));
+            }
+            _ => {}
+        }
+
+        // Other pieces only matter for adjacency, so the other regions have a fixed size.
+        if !matches!(self.region, Region::AdjacentTo(_)) {
+            let squares = board
+                .deployment_zone()
+                .filter(|pos| self.contains(pos, &[], board))
+                .count();
+
+            if needed > squares {
+                return Err(unsatisfiable(format!(
+                    "{needed} pieces don't fit on {squares} squares"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Constraint {
+    type Err = ConstraintError;
+
+    fn from_str(text: &str) -> Result<Constraint, ConstraintError> {
+        let invalid = || ConstraintError::InvalidConstraint {
+            text: text.trim().to_string(),
+        };
+
+        let lowercase = text.to_lowercase();
+        let mut words = lowercase
+            .split_whitespace()
+            .filter(|word| !matches!(*word, "the" | "is" | "are" | "must" | "be"))
+            .peekable();
+
+        let quantity = match words.peek().copied() {
+            Some("a" | "an" | "one") => {
+                words.next();
+                Quantity::AtLeast(1)
+            }
+            Some("no") => {
+                words.next();
+                Quantity::AtMost(0)
+            }
+            Some("every" | "all") => {
+                words.next();
+                Quantity::All
+            }
+            Some("at") => {
+                let bound = words.nth(1).ok_or_else(invalid)?;
+                let count = words.next().and_then(parse_number).ok_or_else(invalid)?;
+
+                match bound {
+                    "least" => Quantity::AtLeast(count),
+                    "most" => Quantity::AtMost(count),
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => Quantity::All,
+        };
+
+        let rank = parse_rank(words.next().ok_or_else(invalid)?)?;
+
+        let negated = words.next_if_eq(&"not").is_some();
+
+        let region = match (words.next(), words.next()) {
+            (Some("on"), Some("row")) => {
+                Region::Row(words.next().and_then(parse_index).ok_or_else(invalid)?)
+            }
+            (Some("on"), Some("front")) if words.next() == Some("row") => Region::FrontRow,
+            (Some("on"), Some("back")) if words.next() == Some("row") => Region::Row(0),
+            (Some("in"), Some("column")) => {
+                Region::Column(words.next().and_then(parse_index).ok_or_else(invalid)?)
+            }
+            (Some("adjacent" | "next"), Some("to")) => {
+                words.next_if(|word| matches!(*word, "a" | "an"));
+                Region::AdjacentTo(parse_rank(words.next().ok_or_else(invalid)?)?)
+            }
+            _ => return Err(invalid()),
+        };
+
+        if words.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Constraint {
+            rank,
+            quantity,
+            region,
+            negated,
+        })
+    }
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.quantity {
+            Quantity::All => write!(f, "{:?}", self.rank)?,
+            Quantity::AtLeast(count) => write!(f, "at least {count} {:?}", self.rank)?,
+            Quantity::AtMost(count) => write!(f, "at most {count} {:?}", self.rank)?,
+        }
+
+        if self.negated {
+            write!(f, " not")?;
+        }
+
+        match self.region {
+            Region::Row(row) => write!(f, " on row {row}"),
+            Region::FrontRow => write!(f, " on the front row"),
+            Region::Column(column) => write!(f, " in column {column}"),
+            Region::AdjacentTo(rank) => write!(f, " adjacent to the {rank:?}"),
+        }
+    }
+}
+
+// Rank names are case insensitive and can be plural.
+fn parse_rank(word: &str) -> Result<Rank, ConstraintError> {
+    let singular = match word {
+        "spies" => "spy",
+        word => word.strip_suffix('s').unwrap_or(word),
+    };
+
+    ALL_RANKS
+        .into_iter()
+        .find(|rank| {
+            let name = format!("{rank:?}").to_lowercase();
+            name == word || name == singular
+        })
+        .ok_or_else(|| ConstraintError::UnknownRank {
+            name: word.to_string(),
+        })
+}
+
+fn parse_number(word: &str) -> Option<usize> {
+    let number = match word {
+        "zero" => 0,
+        "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        word => word.parse().ok()?,
+    };
+
+    Some(number)
+}
+
+fn parse_index(word: &str) -> Option<u8> {
+    parse_number(word)?.try_into().ok()
+}
+
+// A set of constraints that placements are sampled from. In text, there is one constraint per
+// line, and empty lines and lines starting with '#' are skipped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SetupConstraints {
+    pub constraints: Vec<Constraint>,
+}
+
+impl FromStr for SetupConstraints {
+    type Err = ConstraintError;
+
+    fn from_str(text: &str) -> Result<SetupConstraints, ConstraintError> {
+        let constraints = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+
+        Ok(SetupConstraints { constraints })
+    }
+}
+
+impl SetupConstraints {
+    pub fn from_file(path: &Path) -> anyhow::Result<SetupConstraints> {
+        let file = std::fs::read_to_string(path)
+            .with_context(|| format!("Can't read setup constraints from {}", path.display()))?;
+
+        file.parse()
+            .with_context(|| format!("Invalid setup constraints in {}", path.display()))
+    }
+
+    pub fn is_satisfied_by(&self, placement: &[(Rank, Position)], board: &Board) -> bool {
+        self.violations(placement, board) == 0
+    }
+
+    fn violations(&self, placement: &[(Rank, Position)], board: &Board) -> usize {
+        self.constraints
+            .iter()
+            .map(|constraint| constraint.violations(placement, board))
+            .sum()
+    }
+
+    // Samples a placement for player 0 that satisfies every constraint. The search starts from a
+    // `random_placement` and swaps the contents of two squares at a time, keeping the swaps that
+    // don't break more constraints than they fix. Constraints that can't hold on their own are
+    // reported as unsatisfiable before searching, and conflicts between constraints when the
+    // search keeps failing.
+    pub fn sample<R: Rng>(
+        &self,
+        rng: &mut R,
+        army: &Army,
+        board: &Board,
+    ) -> Result<Vec<(Rank, Position)>, ConstraintError> {
+        for constraint in &self.constraints {
+            constraint.check(army, board)?;
+        }
+
+        let squares = board.deployment_zone().collect::<Vec<_>>();
+
+        for _ in 0..RESTARTS {
+            let mut placement = random_placement(rng, army, board);
+            let mut violations = self.violations(&placement, board);
+
+            // The free squares take part in the swaps as well, as pieces without a rank.
+            let mut empty = squares
+                .iter()
+                .filter(|pos| !placement.iter().any(|(_, other)| other == *pos))
+                .copied()
+                .collect::<Vec<_>>();
+
+            for _ in 0..SWAPS_PER_RESTART {
+                if violations == 0 {
+                    return Ok(placement);
+                }
+
+                let a = rng.gen_range(0..placement.len());
+                let b = rng.gen_range(0..placement.len() + empty.len());
+
+                let swap = |placement: &mut Vec<(Rank, Position)>, empty: &mut Vec<Position>| {
+                    if b < placement.len() {
+                        let pos = placement[a].1;
+                        placement[a].1 = placement[b].1;
+                        placement[b].1 = pos;
+                    } else {
+                        let idx = b - placement.len();
+                        std::mem::swap(&mut placement[a].1, &mut empty[idx]);
+                    }
+                };
+
+                swap(&mut placement, &mut empty);
+                let new_violations = self.violations(&placement, board);
+
+                // Getting worse now and then keeps the search from getting stuck.
+                if new_violations <= violations || rng.gen_bool(0.05) {
+                    violations = new_violations;
+                } else {
+                    swap(&mut placement, &mut empty);
+                }
+            }
+
+            if violations == 0 {
+                return Ok(placement);
+            }
+        }
+
+        Err(ConstraintError::NoPlacementFound { attempts: RESTARTS })
+    }
+}
+
+// Deploys a placement sampled from the constraints, and leaves the actions to another bot. The
+// constraints are checked against the ruleset up front, so only a sample that fails by bad luck, or
+// a game with another ruleset, falls back to the other bot's placement.
+pub struct ConstrainedSetupBot {
+    constraints: SetupConstraints,
+    rng: Xoshiro256StarStar,
+    bot: Box<dyn Bot>,
+}
+
+impl ConstrainedSetupBot {
+    // Fails when no placement for the ruleset satisfies the constraints.
+    pub fn new(
+        constraints: SetupConstraints,
+        ruleset: &Ruleset,
+        bot: Box<dyn Bot>,
+        seed: u64,
+    ) -> Result<ConstrainedSetupBot, ConstraintError> {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
+        constraints.sample(&mut rng, &ruleset.armies[0], &ruleset.board)?;
+
+        Ok(ConstrainedSetupBot {
+            constraints,
+            rng,
+            bot,
+        })
+    }
+
+    pub fn from_file(
+        path: &Path,
+        ruleset: &Ruleset,
+        bot: Box<dyn Bot>,
+        seed: u64,
+    ) -> anyhow::Result<ConstrainedSetupBot> {
+        let constraints = SetupConstraints::from_file(path)?;
+
+        ConstrainedSetupBot::new(constraints, ruleset, bot, seed)
+            .with_context(|| format!("The setup constraints in {} can't be used", path.display()))
+    }
+}
+
+impl Bot for ConstrainedSetupBot {
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+        self.constraints
+            .sample(&mut self.rng, &ruleset.armies[0], &ruleset.board)
+            .unwrap_or_else(|_| self.bot.get_initial_placements(ruleset))
+    }
+
+    fn get_action(&mut self, state: State, beliefs: &Beliefs) -> Action {
+        self.bot.get_action(state, beliefs)
+    }
+
+    fn offers_draw(&mut self, state: State) -> bool {
+        self.bot.offers_draw(state)
+    }
+
+    fn accepts_draw(&mut self, state: State) -> bool {
+        self.bot.accepts_draw(state)
+    }
+
+    fn on_game_end(&mut self, result: GameResult) {
+        self.bot.on_game_end(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::RandoBot;
+    use crate::game::validate_placement;
+
+    #[test]
+    fn test_samples_satisfy_the_constraints() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(8);
+        let ruleset = Ruleset::classic();
+        let (army, board) = (&ruleset.armies[0], &ruleset.board);
+
+        let constraints = "
+            # A cautious setup.
+            Flag on row 0
+            a Bomb adjacent to the Flag
+            Marshal not in column 0
+            at least one Scout on the front row
+            at most 2 Bombs on the front row
+        "
+        .parse::<SetupConstraints>()
+        .unwrap();
+
+        assert_eq!(
+            constraints.constraints[1],
+            Constraint {
+                rank: Rank::Bomb,
+                quantity: Quantity::AtLeast(1),
+                region: Region::AdjacentTo(Rank::Flag),
+                negated: false,
+            }
+        );
+
+        for _ in 0..5 {
+            let placement = constraints.sample(&mut rng, army, board).unwrap();
+            validate_placement(&placement, army, board, 0).unwrap();
+            assert!(constraints.is_satisfied_by(&placement, board));
+        }
+
+        let mut unsatisfiable = |text: &str| {
+            let constraints = text.parse::<SetupConstraints>().unwrap();
+            constraints.sample(&mut rng, army, board).unwrap_err()
+        };
+
+        assert!(matches!(
+            unsatisfiable("Flag on row 4"),
+            ConstraintError::Unsatisfiable { .. }
+        ));
+        assert!(matches!(
+            unsatisfiable("at least 9 Scouts on the front row"),
+            ConstraintError::Unsatisfiable { .. }
+        ));
+        assert!(matches!(
+            unsatisfiable("Flag on row 0\nFlag on row 1"),
+            ConstraintError::NoPlacementFound { .. }
+        ));
+
+        assert!(matches!(
+            "Flag under a tree".parse::<Constraint>(),
+            Err(ConstraintError::InvalidConstraint { .. })
+        ));
+    }
+
+    #[test]
+    fn test_bot_deploys_from_a_constraints_file() {
+        let ruleset = Ruleset::barrage();
+        let path = std::env::temp_dir().join(format!("constraints-{}.txt", std::process::id()));
+
+        std::fs::write(&path, "Flag on row 0\nMarshal on the front row\n").unwrap();
+        let bot = ConstrainedSetupBot::from_file(&path, &ruleset, Box::new(RandoBot::new(1)), 2);
+        std::fs::remove_file(&path).unwrap();
+
+        let mut bot = bot.unwrap();
+        let placement = bot.get_initial_placements(&ruleset);
+        validate_placement(&placement, &ruleset.armies[0], &ruleset.board, 0).unwrap();
+        assert!(bot.constraints.is_satisfied_by(&placement, &ruleset.board));
+
+        // Constraints that no placement satisfies are reported before the game.
+        let constraints = |text: &str| text.parse::<SetupConstraints>().unwrap();
+        let new_bot = |text| {
+            ConstrainedSetupBot::new(constraints(text), &ruleset, Box::new(RandoBot::new(1)), 2)
+        };
+        assert!(matches!(
+            new_bot("Flag on row 9").err(),
+            Some(ConstraintError::Unsatisfiable { .. })
+        ));
+        assert!(matches!(
+            new_bot("Flag on row 0\nFlag on row 1").err(),
+            Some(ConstraintError::NoPlacementFound { .. })
+        ));
+    }
+}
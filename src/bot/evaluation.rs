@@ -0,0 +1,206 @@
+use crate::game::logic::all_possible_moves;
+use crate::game::{Beliefs, Piece, Rank, State, ALL_RANKS, MATERIAL_VALUES, RANK_COUNT};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// A static evaluation of a state, which search bots use when they can't look any further ahead.
+pub trait Evaluator {
+    // How good the state is for player 0, in the same units as the values of the ranks. The
+    // beliefs are used for the pieces whose rank is unknown, when they are about their owner.
+    fn evaluate(&self, state: &State, beliefs: Option<&Beliefs>) -> f32;
+
+    // The evaluation squashed to be between -1 and 1, like the results of finished games, where
+    // being ahead by a marshal is most of the way to a win.
+    fn normalized(&self, state: &State, beliefs: Option<&Beliefs>) -> f32 {
+        (self.evaluate(state, beliefs) / 10.0).tanh()
+    }
+}
+
+// The weights of the features of `WeightedEvaluator`. Every feature is the difference between the
+// two players. Missing fields in a config file get their default value.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    // Spy, Scout, Miner, Sergeant, Lieutenant, Captain, Major, Colonel, General, Marshal, Bomb,
+    // Flag.
+    pub rank_values: [f32; RANK_COUNT],
+    pub material: f32,
+    // Per possible move.
+    pub mobility: f32,
+    // Per enemy piece near the flag, and once more when the enemy knows where the flag is.
+    pub flag_safety: f32,
+    // Per piece with a rank the opponent doesn't know.
+    pub information: f32,
+    // Per row a moveable piece has moved towards the opponent.
+    pub advancement: f32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            rank_values: MATERIAL_VALUES.map(|value| value as f32),
+            material: 1.0,
+            mobility: 0.02,
+            flag_safety: 0.5,
+            information: 0.2,
+            advancement: 0.05,
+        }
+    }
+}
+
+impl Weights {
+    // Reads the weights from a JSON file.
+    pub fn from_file(path: &Path) -> Result<Weights> {
+        let file = std::fs::read_to_string(path)
+            .with_context(|| format!("Can't read evaluation weights from {}", path.display()))?;
+
+        serde_json::from_str(&file)
+            .with_context(|| format!("Invalid evaluation weights in {}", path.display()))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WeightedEvaluator {
+    pub weights: Weights,
+}
+
+impl WeightedEvaluator {
+    pub fn new(weights: Weights) -> WeightedEvaluator {
+        WeightedEvaluator { weights }
+    }
+
+    pub fn from_file(path: &Path) -> Result<WeightedEvaluator> {
+        Ok(WeightedEvaluator::new(Weights::from_file(path)?))
+    }
+
+    fn rank_value(&self, rank: Rank) -> f32 {
+        self.weights
+            .rank_values
+            .get(rank as usize)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    fn material(&self, state: &State, player_id: usize, beliefs: Option<&Beliefs>) -> f32 {
+        let beliefs = beliefs.filter(|beliefs| beliefs.player_id == player_id);
+
+        // Without beliefs, an unknown piece is worth as much as the average of the army.
+        let army = &state.ruleset.armies[player_id];
+        let average_value = ALL_RANKS
+            .iter()
+            .map(|&rank| self.rank_value(rank) * army.count(rank) as f32)
+            .sum::<f32>()
+            / army.len().max(1) as f32;
+
+        state.pieces[player_id]
+            .iter()
+            .map(|piece| match (piece.rank, beliefs) {
+                (Rank::Unknown, Some(beliefs)) => ALL_RANKS
+                    .iter()
+                    .map(|&rank| beliefs.probability(piece, rank) * self.rank_value(rank))
+                    .sum(),
+                (Rank::Unknown, None) => average_value,
+                (rank, _) => self.rank_value(rank),
+            })
+            .sum()
+    }
+
+    fn mobility(&self, state: &State, player_id: usize) -> f32 {
+        let mut state = *state;
+        if state.current_player_id != player_id {
+            state.pass_turn();
+        }
+
+        let mut actions = Vec::new();
+        all_possible_moves(&state, &mut actions);
+        actions.len() as f32
+    }
+
+    // Minus the number of enemy pieces close to the flag, and one more when its rank is known.
+    // A flag that the evaluation can't see counts as safe.
+    fn flag_safety(&self, state: &State, player_id: usize) -> f32 {
+        let Some(flag) = state.pieces[player_id]
+            .iter()
+            .find(|piece| piece.rank == Rank::Flag)
+        else {
+            return 0.0;
+        };
+
+        let threats = state.pieces[1 - player_id]
+            .iter()
+            .filter(|piece| piece.pos.manhattan_distance(&flag.pos) <= 2)
+            .count();
+
+        -(threats as f32) - flag.is_revealed as u8 as f32
+    }
+
+    fn information(&self, state: &State, player_id: usize) -> f32 {
+        state.pieces[player_id]
+            .iter()
+            .filter(|piece| !piece.is_revealed)
+            .count() as f32
+    }
+
+    fn advancement(&self, state: &State, player_id: usize) -> f32 {
+        let height = state.ruleset.board.height;
+
+        state.pieces[player_id]
+            .iter()
+            .filter(|piece| piece.rank.is_moveable())
+            .map(|Piece { pos, .. }| match player_id {
+                0 => pos.y,
+                _ => height - 1 - pos.y,
+            } as f32)
+            .sum()
+    }
+}
+
+impl Evaluator for WeightedEvaluator {
+    fn evaluate(&self, state: &State, beliefs: Option<&Beliefs>) -> f32 {
+        let weights = &self.weights;
+        let difference = |feature: &dyn Fn(usize) -> f32| feature(0) - feature(1);
+
+        let mut score = weights.material * difference(&|id| self.material(state, id, beliefs))
+            + weights.flag_safety * difference(&|id| self.flag_safety(state, id))
+            + weights.information * difference(&|id| self.information(state, id))
+            + weights.advancement * difference(&|id| self.advancement(state, id));
+
+        // Generating the moves is by far the slowest part, so it's skipped when it doesn't count.
+        if weights.mobility != 0.0 {
+            score += weights.mobility * difference(&|id| self.mobility(state, id));
+        }
+
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Ruleset;
+    use crate::testing::random_start;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    #[test]
+    fn test_evaluation_is_symmetric_and_weights_can_be_partial() {
+        let weights = serde_json::from_str::<Weights>(r#"{ "mobility": 0.5 }"#).unwrap();
+        assert_eq!(weights.mobility, 0.5);
+        assert_eq!(weights.material, Weights::default().material);
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let ruleset = Ruleset::barrage();
+        let mut state = random_start(&mut rng, ruleset);
+
+        let evaluator = WeightedEvaluator::new(weights);
+        let evaluation = evaluator.evaluate(&state, None);
+        let reversed = evaluator.evaluate(&state.reversed(), None);
+        assert!((evaluation + reversed).abs() < 1e-4);
+
+        // Losing the marshal makes things worse for its owner.
+        state.pieces[1].retain(|piece| piece.rank != Rank::Marshal);
+        assert!(evaluator.evaluate(&state, None) > evaluation);
+        assert!(evaluator.normalized(&state, None) < 1.0);
+    }
+}
@@ -0,0 +1,525 @@
+use crate::bot::{
+    captures_flag, heuristic_placement, pick_randomly, Bot, Budget, Evaluator, WeightedEvaluator,
+};
+use crate::game::logic::{all_possible_moves, battle_casualties};
+use crate::game::{
+    Action, Beliefs, Determinizer, Piece, Position, Rank, Ruleset, State, ALL_RANKS, MAX_PIECES,
+};
+use rand::{seq::SliceRandom, RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+const WIN: f32 = 1000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    depth: usize,
+    value: f32,
+    bound: Bound,
+    best: Option<Action>,
+}
+
+// Battle results that cause the same casualties, represented by the ranks of the likeliest one.
+#[derive(Debug, Clone, Copy)]
+struct OutcomeGroup {
+    probability: f32,
+    likeliest: f32,
+    ranks: [Rank; 2],
+}
+
+// The search was stopped because the time ran out.
+struct OutOfTime;
+
+// A depth-limited search. The players take turns maximizing their own score with alpha-beta
+// pruning, and battles against pieces of unknown rank are chance nodes, where every possible
+// result of the battle is weighted by how likely the unknown rank is to cause it. The search is
+// repeated with increasing depth until the time runs out.
+//
+// Which enemy pieces can move, and how far, depends on their hidden ranks. Every turn those ranks
+// are sampled once from the beliefs, so that pieces that may be bombs or flags don't all move in
+// the search, and pieces that may be scouts can move far.
+pub struct ExpectimaxBot {
+    rng: Xoshiro256StarStar,
+    determinizer: Determinizer,
+    // The sampled ranks of the enemy pieces, by piece id.
+    sampled_ranks: [Rank; MAX_PIECES],
+    budget: Budget,
+    transpositions: HashMap<u64, Entry>,
+    evaluator: Box<dyn Evaluator>,
+    beliefs: Option<Beliefs>,
+    start: Instant,
+    nodes: usize,
+}
+
+impl ExpectimaxBot {
+    pub fn new(seed: u64) -> ExpectimaxBot {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
+        let determinizer = Determinizer::new(rng.next_u64());
+
+        ExpectimaxBot {
+            rng,
+            determinizer,
+            sampled_ranks: [Rank::Unknown; MAX_PIECES],
+            budget: Budget {
+                iterations: 8,
+                time: Some(Duration::from_millis(500)),
+            },
+            transpositions: HashMap::new(),
+            evaluator: Box::new(WeightedEvaluator::default()),
+            beliefs: None,
+            start: Instant::now(),
+            nodes: 0,
+        }
+    }
+
+    // The iterations of the budget are the maximum search depth.
+    pub fn with_budget(mut self, budget: Budget) -> ExpectimaxBot {
+        self.budget = budget;
+        self
+    }
+
+    pub fn with_evaluator(mut self, evaluator: Box<dyn Evaluator>) -> ExpectimaxBot {
+        self.evaluator = evaluator;
+        self
+    }
+
+    fn search(
+        &mut self,
+        state: &mut State,
+        depth: usize,
+        mut alpha: f32,
+        beta: f32,
+    ) -> Result<f32, OutOfTime> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(1024) && self.budget.is_spent(0, self.start) {
+            return Err(OutOfTime);
+        }
+
+        if depth == 0 {
+            return Ok(self.evaluate(state));
+        }
+
+        let key = state.position_key();
+        let entry = self.transpositions.get(&key).copied();
+        if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+            match entry.bound {
+                Bound::Exact => return Ok(entry.value),
+                Bound::Lower if entry.value >= beta => return Ok(entry.value),
+                Bound::Upper if entry.value <= alpha => return Ok(entry.value),
+                _ => {}
+            }
+        }
+
+        let mut actions = Vec::new();
+        self.possible_moves(state, &mut actions);
+
+        if actions.is_empty() {
+            return Ok(if state.ruleset.end.immobile_player_loses {
+                -WIN
+            } else {
+                self.evaluate(state)
+            });
+        }
+
+        order_actions(state, &mut actions, entry.and_then(|entry| entry.best));
+
+        let original_alpha = alpha;
+        let mut best_value = f32::NEG_INFINITY;
+        let mut best_action = None;
+
+        for action in actions {
+            let value = self.action_value(state, &action, depth, alpha, beta)?;
+
+            if value > best_value {
+                best_value = value;
+                best_action = Some(action);
+            }
+
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_value <= original_alpha {
+            Bound::Upper
+        } else if best_value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+
+        self.transpositions.insert(
+            key,
+            Entry {
+                depth,
+                value: best_value,
+                bound,
+                best: best_action,
+            },
+        );
+
+        Ok(best_value)
+    }
+
+    // The moves of the current player, with the sampled ranks in place of the unknown ones.
+    fn possible_moves(&self, state: &State, actions: &mut Vec<Action>) {
+        let id = state.current_player_id;
+
+        if state.pieces[id]
+            .iter()
+            .all(|piece| piece.rank != Rank::Unknown)
+        {
+            all_possible_moves(state, actions);
+            return;
+        }
+
+        let mut sampled = *state;
+        for piece in sampled.pieces[id].iter_mut() {
+            if piece.rank == Rank::Unknown {
+                piece.rank = self.sampled_ranks[piece.id as usize];
+            }
+        }
+
+        all_possible_moves(&sampled, actions);
+    }
+
+    // The value of the action for the player who makes it.
+    fn action_value(
+        &mut self,
+        state: &mut State,
+        action: &Action,
+        depth: usize,
+        alpha: f32,
+        beta: f32,
+    ) -> Result<f32, OutOfTime> {
+        let outcomes = self.battle_outcomes(state, action);
+
+        // Known ranks on both sides, or no battle at all.
+        if outcomes.is_empty() {
+            let Ok(undo) = state.apply_action(action) else {
+                return Ok(f32::NEG_INFINITY);
+            };
+
+            let value = if captures_flag(&undo.turn) {
+                WIN
+            } else {
+                -self.search(state, depth - 1, -beta, -alpha)?
+            };
+
+            state.unmake(&undo);
+            return Ok(value);
+        }
+
+        let mut expected_value = 0.0;
+
+        for (probability, ranks) in outcomes {
+            let mut outcome_state = *state;
+            for (player_id, pos) in [
+                (state.current_player_id, action.from),
+                (1 - state.current_player_id, action.to),
+            ] {
+                if let Some(piece) = outcome_state.pieces[player_id]
+                    .iter_mut()
+                    .find(|piece| piece.pos == pos)
+                {
+                    piece.rank = ranks[player_id];
+                }
+            }
+            outcome_state.hash = outcome_state.compute_hash();
+
+            let Ok(undo) = outcome_state.apply_action(action) else {
+                continue;
+            };
+
+            let value = if captures_flag(&undo.turn) {
+                WIN
+            } else {
+                -self.search(
+                    &mut outcome_state,
+                    depth - 1,
+                    f32::NEG_INFINITY,
+                    f32::INFINITY,
+                )?
+            };
+
+            expected_value += probability * value;
+        }
+
+        Ok(expected_value)
+    }
+
+    // The possible results of a battle against a piece of unknown rank, as the probability and
+    // the ranks of both pieces. Ranks that lead to the same casualties are merged into their most
+    // likely rank. Empty when there is no battle, or when the ranks of both pieces are known.
+    fn battle_outcomes(&self, state: &State, action: &Action) -> Vec<(f32, [Rank; 2])> {
+        let id = state.current_player_id;
+        let other_id = 1 - id;
+
+        let (Some(attacker), Some(defender)) = (
+            state.piece_at(id, &action.from),
+            state.piece_at(other_id, &action.to),
+        ) else {
+            return Vec::new();
+        };
+
+        if attacker.rank != Rank::Unknown && defender.rank != Rank::Unknown {
+            return Vec::new();
+        }
+
+        // Only a scout can attack from further away than the next square.
+        let attacker_ranks = self.rank_distribution(attacker, |rank| {
+            rank.is_moveable() && (action.distance() == 1 || rank == Rank::Scout)
+        });
+        let defender_ranks = self.rank_distribution(defender, |_| true);
+
+        // Indexed by which of the pieces dies.
+        let mut groups: [[Option<OutcomeGroup>; 2]; 2] = [[None; 2]; 2];
+        let mut flag_capture: Option<OutcomeGroup> = None;
+
+        for (attacker_rank, attacker_probability) in attacker_ranks {
+            for &(defender_rank, defender_probability) in &defender_ranks {
+                let probability = attacker_probability * defender_probability;
+
+                let mut ranks = [attacker_rank, defender_rank];
+                ranks.as_mut_slice().swap(0, id);
+
+                let group = if defender_rank == Rank::Flag {
+                    &mut flag_capture
+                } else {
+                    let Ok((defender_died, attacker_died)) =
+                        battle_casualties(&state.ruleset.battle, &defender_rank, &attacker_rank)
+                    else {
+                        continue;
+                    };
+                    &mut groups[attacker_died as usize][defender_died as usize]
+                };
+
+                let group = group.get_or_insert(OutcomeGroup {
+                    probability: 0.0,
+                    likeliest: 0.0,
+                    ranks,
+                });
+
+                group.probability += probability;
+                if probability > group.likeliest {
+                    group.likeliest = probability;
+                    group.ranks = ranks;
+                }
+            }
+        }
+
+        let outcomes = groups
+            .into_iter()
+            .flatten()
+            .chain([flag_capture])
+            .flatten()
+            .map(|group| (group.probability, group.ranks))
+            .collect::<Vec<_>>();
+
+        let total = outcomes
+            .iter()
+            .map(|(probability, _)| probability)
+            .sum::<f32>();
+
+        outcomes
+            .into_iter()
+            .map(|(probability, ranks)| (probability / total, ranks))
+            .collect()
+    }
+
+    // The ranks the piece could have out of the allowed ones, with their probabilities.
+    fn rank_distribution<F: Fn(Rank) -> bool>(
+        &self,
+        piece: &Piece,
+        allowed: F,
+    ) -> Vec<(Rank, f32)> {
+        if piece.rank != Rank::Unknown {
+            return vec![(piece.rank, 1.0)];
+        }
+
+        let distribution = ALL_RANKS
+            .into_iter()
+            .filter(|rank| allowed(*rank))
+            .map(|rank| {
+                let probability = match &self.beliefs {
+                    Some(beliefs) => beliefs.probability(piece, rank),
+                    None => 1.0,
+                };
+                (rank, probability)
+            })
+            .filter(|(_, probability)| *probability > 0.0)
+            .collect::<Vec<_>>();
+
+        if distribution.is_empty() {
+            return vec![(Rank::Unknown, 1.0)];
+        }
+
+        distribution
+    }
+
+    // The evaluation for the player to move.
+    fn evaluate(&self, state: &State) -> f32 {
+        let evaluation = self.evaluator.evaluate(state, self.beliefs.as_ref());
+
+        if state.current_player_id == 0 {
+            evaluation
+        } else {
+            -evaluation
+        }
+    }
+}
+
+impl Bot for ExpectimaxBot {
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+        heuristic_placement(&mut self.rng, &ruleset.armies[0], &ruleset.board)
+    }
+
+    fn get_action(&mut self, state: State, beliefs: &Beliefs) -> Action {
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.beliefs = Some(*beliefs);
+
+        // The beliefs change every turn, so the old values can't be trusted.
+        self.transpositions.clear();
+
+        // Without a consistent sample the unknown pieces move like any other piece.
+        self.sampled_ranks = [Rank::Unknown; MAX_PIECES];
+        if let Ok(sample) = self
+            .determinizer
+            .sample_weighted(&state, 1, |piece, rank| beliefs.probability(piece, rank))
+        {
+            for piece in &sample.pieces[1] {
+                self.sampled_ranks[piece.id as usize] = piece.rank;
+            }
+        }
+
+        let mut actions = Vec::new();
+        all_possible_moves(&state, &mut actions);
+
+        // Equally good actions are picked at random, because the order is kept when sorting.
+        actions.shuffle(&mut self.rng);
+
+        let mut best_action = *pick_randomly(&mut self.rng, &actions);
+        let mut state = state;
+
+        for depth in 1..=self.budget.iterations {
+            let mut best_value = f32::NEG_INFINITY;
+            let mut best_at_depth = None;
+
+            let previous_best = self
+                .transpositions
+                .get(&state.position_key())
+                .and_then(|entry| entry.best);
+            order_actions(&state, &mut actions, previous_best);
+
+            let mut out_of_time = false;
+
+            for action in actions.iter() {
+                match self.action_value(&mut state, action, depth, best_value, f32::INFINITY) {
+                    Ok(value) if value > best_value => {
+                        best_value = value;
+                        best_at_depth = Some(*action);
+                    }
+                    Ok(_) => {}
+                    Err(OutOfTime) => {
+                        out_of_time = true;
+                        break;
+                    }
+                }
+            }
+
+            if out_of_time {
+                break;
+            }
+
+            if let Some(action) = best_at_depth {
+                best_action = action;
+                self.transpositions.insert(
+                    state.position_key(),
+                    Entry {
+                        depth,
+                        value: best_value,
+                        bound: Bound::Exact,
+                        best: Some(action),
+                    },
+                );
+            }
+
+            if best_value >= WIN || self.budget.is_spent(0, self.start) {
+                break;
+            }
+        }
+
+        best_action
+    }
+}
+
+// Tries the best action of a previous search first, and then the attacks.
+fn order_actions(state: &State, actions: &mut [Action], best: Option<Action>) {
+    let other_id = 1 - state.current_player_id;
+
+    actions.sort_by_key(|action| {
+        if Some(*action) == best {
+            0
+        } else if state.bitmaps[other_id].get(action.to.to_bit_index(&state.ruleset.board)) {
+            1
+        } else {
+            2
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_beats_simple_bots, assert_plays_legally, random_start};
+
+    #[test]
+    fn test_enemy_moves_follow_the_sampled_ranks() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        let mut state = random_start(&mut rng, Ruleset::barrage()).obscured_for(0);
+        state.pass_turn();
+
+        let mut bot = ExpectimaxBot::new(1);
+        let moves = |bot: &ExpectimaxBot| {
+            let mut actions = Vec::new();
+            bot.possible_moves(&state, &mut actions);
+            actions.len()
+        };
+
+        let unknown = moves(&bot);
+        bot.sampled_ranks = [Rank::Bomb; MAX_PIECES];
+        assert_eq!(moves(&bot), 0);
+        bot.sampled_ranks = [Rank::Scout; MAX_PIECES];
+        assert!(moves(&bot) > unknown);
+    }
+
+    fn bot(depth: usize) -> impl Fn(u64) -> Box<dyn Bot> {
+        let budget = Budget {
+            iterations: depth,
+            time: None,
+        };
+        move |seed| Box::new(ExpectimaxBot::new(seed).with_budget(budget))
+    }
+
+    #[test]
+    fn test_only_makes_legal_actions() {
+        assert_plays_legally(&bot(2));
+    }
+
+    // Too slow for a debug build, run it with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_beats_the_simple_bots() {
+        assert_beats_simple_bots(&bot(4), 20, [0.7, 0.9]);
+    }
+}
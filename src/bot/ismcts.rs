@@ -0,0 +1,367 @@
+use crate::boardbitmap::BoardBitmap;
+use crate::bot::rollout::{captures_flag, rollout, win_score, RolloutPolicy};
+use crate::bot::{heuristic_placement, pick_randomly, Bot, Budget, Evaluator, WeightedEvaluator};
+use crate::game::logic::all_possible_moves;
+use crate::game::{Action, Beliefs, Determinizer, Position, Rank, Ruleset, State};
+use rand::{seq::SliceRandom, RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use std::time::Instant;
+
+struct Node {
+    // The action that lead to this node, and the player who made it. The root has no action.
+    action: Option<Action>,
+    player_id: usize,
+    children: Vec<usize>,
+    visits: u32,
+    // How often the node could have been picked, which isn't the same for all children because
+    // the possible actions depend on the sampled ranks.
+    availability: u32,
+    // The sum of the results of all visits, for the player who made the action.
+    score: f32,
+}
+
+impl Node {
+    fn new(action: Option<Action>, player_id: usize) -> Node {
+        Node {
+            action,
+            player_id,
+            children: Vec::new(),
+            visits: 0,
+            availability: 0,
+            score: 0.0,
+        }
+    }
+
+    fn upper_confidence_bound(&self, exploration: f32) -> f32 {
+        let visits = self.visits as f32;
+        let availability = self.availability.max(1) as f32;
+
+        self.score / visits + exploration * (availability.ln() / visits).sqrt()
+    }
+}
+
+// What is needed to find the part of the previous tree that is still relevant on the next turn.
+struct Reusable {
+    nodes: Vec<Node>,
+    // The node of the action that the bot made.
+    node: usize,
+    enemy_bitmap: BoardBitmap,
+    action: Action,
+}
+
+// Information set Monte Carlo tree search. Every iteration samples the hidden enemy ranks, and then
+// walks down a single tree that is shared by all samples, only following the actions that are
+// possible in the sample. The action that was tried the most at the root is picked.
+pub struct IsmctsBot {
+    rng: Xoshiro256StarStar,
+    determinizer: Determinizer,
+    budget: Budget,
+    exploration: f32,
+    evaluator: Box<dyn Evaluator>,
+    rollout_policy: RolloutPolicy,
+    rollout_depth: usize,
+    reuse_tree: bool,
+    previous: Option<Reusable>,
+    action_buffer: Vec<Action>,
+    rollout_buffer: Vec<Action>,
+}
+
+impl IsmctsBot {
+    pub fn new(seed: u64) -> IsmctsBot {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
+        let determinizer = Determinizer::new(rng.next_u64());
+
+        IsmctsBot {
+            rng,
+            determinizer,
+            budget: Budget {
+                iterations: 1000,
+                ..Budget::default()
+            },
+            exploration: 0.7,
+            evaluator: Box::new(WeightedEvaluator::default()),
+            rollout_policy: RolloutPolicy::GreedyCaptures,
+            rollout_depth: 20,
+            reuse_tree: false,
+            previous: None,
+            action_buffer: Vec::new(),
+            rollout_buffer: Vec::new(),
+        }
+    }
+
+    pub fn with_budget(mut self, budget: Budget) -> IsmctsBot {
+        self.budget = budget;
+        self
+    }
+
+    pub fn with_exploration(mut self, exploration: f32) -> IsmctsBot {
+        self.exploration = exploration;
+        self
+    }
+
+    // Scores the rollouts that don't reach the end of the game.
+    pub fn with_evaluator(mut self, evaluator: Box<dyn Evaluator>) -> IsmctsBot {
+        self.evaluator = evaluator;
+        self
+    }
+
+    pub fn with_rollouts(mut self, policy: RolloutPolicy, depth: usize) -> IsmctsBot {
+        self.rollout_policy = policy;
+        self.rollout_depth = depth;
+        self
+    }
+
+    // Keeps the part of the tree below the actions that were played since the last turn.
+    pub fn with_tree_reuse(mut self, reuse_tree: bool) -> IsmctsBot {
+        self.reuse_tree = reuse_tree;
+        self
+    }
+
+    // Looks for the node of the action the opponent answered with. It is found by comparing where
+    // the enemy pieces were and are now, and the old tree is dropped when that isn't conclusive.
+    fn reused_tree(&mut self, state: &State) -> Option<Vec<Node>> {
+        let Reusable {
+            nodes,
+            node,
+            enemy_bitmap,
+            action,
+        } = self.previous.take()?;
+
+        let board = &state.ruleset.board;
+        let is_unchanged = |idx: usize| enemy_bitmap.get(idx) == state.bitmaps[1].get(idx);
+
+        let mut matches = nodes[node].children.iter().filter(|&&child| {
+            let Some(Action { from, to }) = nodes[child].action else {
+                return false;
+            };
+
+            let [from, to, attacked] = [from, to, action.to].map(|pos| pos.to_bit_index(board));
+
+            enemy_bitmap.get(from)
+                && !state.bitmaps[1].get(from)
+                && (0..board.square_count())
+                    .filter(|&idx| idx != from && idx != to && idx != attacked)
+                    .all(is_unchanged)
+        });
+
+        let child = *matches.next()?;
+        if matches.next().is_some() {
+            return None;
+        }
+
+        Some(subtree(nodes, child))
+    }
+
+    fn run_iteration(&mut self, nodes: &mut Vec<Node>, state: &State) -> Option<()> {
+        let mut state = self.determinizer.sample(state, 1).ok()?;
+        let mut path = vec![0];
+        let mut result = None;
+
+        loop {
+            let node = *path.last()?;
+
+            self.action_buffer.clear();
+            all_possible_moves(&state, &mut self.action_buffer);
+
+            if self.action_buffer.is_empty() {
+                let id = state.current_player_id;
+                result = Some(if state.ruleset.end.immobile_player_loses {
+                    win_score(1 - id)
+                } else {
+                    self.evaluator.normalized(&state, None)
+                });
+                break;
+            }
+
+            // Every child that is possible in this sample gets a chance to be picked.
+            let available = nodes[node]
+                .children
+                .iter()
+                .copied()
+                .filter(|&child| {
+                    nodes[child]
+                        .action
+                        .is_some_and(|action| self.action_buffer.contains(&action))
+                })
+                .collect::<Vec<_>>();
+
+            for &child in &available {
+                nodes[child].availability += 1;
+            }
+
+            let untried = self
+                .action_buffer
+                .iter()
+                .filter(|action| {
+                    !available
+                        .iter()
+                        .any(|&child| nodes[child].action == Some(**action))
+                })
+                .copied()
+                .collect::<Vec<_>>();
+
+            let expanded = !untried.is_empty();
+
+            let child = if let Some(action) = untried.choose(&mut self.rng) {
+                let mut child = Node::new(Some(*action), state.current_player_id);
+                child.availability = 1;
+                nodes.push(child);
+
+                let child = nodes.len() - 1;
+                nodes[node].children.push(child);
+                child
+            } else {
+                let exploration = self.exploration;
+                *available.iter().max_by(|&&a, &&b| {
+                    nodes[a]
+                        .upper_confidence_bound(exploration)
+                        .total_cmp(&nodes[b].upper_confidence_bound(exploration))
+                })?
+            };
+
+            path.push(child);
+
+            let undo = state.apply_action(&nodes[child].action?).ok()?;
+            if captures_flag(&undo.turn) {
+                result = Some(win_score(undo.turn.player_id));
+                break;
+            }
+
+            if expanded {
+                break;
+            }
+        }
+
+        let result = result.unwrap_or_else(|| {
+            rollout(
+                &mut self.rng,
+                state,
+                self.rollout_policy,
+                self.rollout_depth,
+                self.evaluator.as_ref(),
+                &mut self.rollout_buffer,
+            )
+        });
+
+        for node in path {
+            let node = &mut nodes[node];
+            node.visits += 1;
+            node.score += if node.player_id == 0 { result } else { -result };
+        }
+
+        Some(())
+    }
+}
+
+impl Bot for IsmctsBot {
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+        self.previous = None;
+        heuristic_placement(&mut self.rng, &ruleset.armies[0], &ruleset.board)
+    }
+
+    fn get_action(&mut self, state: State, _beliefs: &Beliefs) -> Action {
+        let start = Instant::now();
+
+        let mut nodes = self
+            .reused_tree(&state)
+            .unwrap_or_else(|| vec![Node::new(None, 1)]);
+
+        let mut iterations = 0;
+        while !self.budget.is_spent(iterations, start) {
+            if self.run_iteration(&mut nodes, &state).is_none() {
+                break;
+            }
+            iterations += 1;
+        }
+
+        self.action_buffer.clear();
+        all_possible_moves(&state, &mut self.action_buffer);
+
+        let best = nodes[0]
+            .children
+            .iter()
+            .filter(|&&child| {
+                nodes[child]
+                    .action
+                    .is_some_and(|action| self.action_buffer.contains(&action))
+            })
+            .max_by_key(|&&child| nodes[child].visits)
+            .copied();
+
+        let Some(best) = best else {
+            self.previous = None;
+            return *pick_randomly(&mut self.rng, &self.action_buffer);
+        };
+
+        let action = nodes[best].action.unwrap_or(self.action_buffer[0]);
+
+        self.previous = self.reuse_tree.then(|| Reusable {
+            nodes,
+            node: best,
+            enemy_bitmap: state.bitmaps[1],
+            action,
+        });
+
+        action
+    }
+}
+
+// Copies the nodes below `root` into a new tree, with `root` as the new root.
+fn subtree(mut nodes: Vec<Node>, root: usize) -> Vec<Node> {
+    let mut tree = Vec::new();
+    let mut stack: Vec<(usize, Option<usize>)> = vec![(root, None)];
+
+    while let Some((old, parent)) = stack.pop() {
+        let mut node = std::mem::replace(&mut nodes[old], Node::new(None, 0));
+        let children = std::mem::take(&mut node.children);
+
+        tree.push(node);
+        let new = tree.len() - 1;
+
+        if let Some(parent) = parent {
+            tree[parent].children.push(new);
+        }
+
+        stack.extend(children.into_iter().map(|child| (child, Some(new))));
+    }
+
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_beats_simple_bots, assert_plays_legally};
+
+    #[test]
+    fn test_only_makes_legal_actions_when_reusing_the_tree() {
+        let budget = Budget {
+            iterations: 50,
+            time: None,
+        };
+
+        assert_plays_legally(&|seed| {
+            Box::new(
+                IsmctsBot::new(seed)
+                    .with_budget(budget)
+                    .with_tree_reuse(true),
+            )
+        });
+    }
+
+    // Too slow for a debug build, run it with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_beats_the_simple_bots() {
+        let budget = Budget {
+            iterations: 1000,
+            time: None,
+        };
+
+        assert_beats_simple_bots(
+            &|seed| Box::new(IsmctsBot::new(seed).with_budget(budget)),
+            20,
+            [0.7, 0.9],
+        );
+    }
+}
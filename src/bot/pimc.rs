@@ -0,0 +1,155 @@
+use crate::bot::rollout::{captures_flag, rollout, RolloutPolicy};
+use crate::bot::{heuristic_placement, pick_randomly, Bot, Budget, Evaluator, WeightedEvaluator};
+use crate::game::logic::all_possible_moves;
+use crate::game::{Action, Beliefs, Determinizer, Position, Rank, Ruleset, State};
+use rand::{RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use std::time::Instant;
+
+// Perfect-information Monte Carlo: every iteration fills in the hidden enemy ranks at random, and
+// then plays out every possible action in that sample as if all ranks were known. The action with
+// the best average result over all samples is picked.
+pub struct PimcBot {
+    rng: Xoshiro256StarStar,
+    determinizer: Determinizer,
+    budget: Budget,
+    evaluator: Box<dyn Evaluator>,
+    rollout_policy: RolloutPolicy,
+    rollout_depth: usize,
+    rollouts_per_action: usize,
+    action_buffer: Vec<Action>,
+    rollout_buffer: Vec<Action>,
+}
+
+impl PimcBot {
+    pub fn new(seed: u64) -> PimcBot {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
+        let determinizer = Determinizer::new(rng.next_u64());
+
+        PimcBot {
+            rng,
+            determinizer,
+            budget: Budget::default(),
+            evaluator: Box::new(WeightedEvaluator::default()),
+            rollout_policy: RolloutPolicy::GreedyCaptures,
+            rollout_depth: 20,
+            rollouts_per_action: 1,
+            action_buffer: Vec::new(),
+            rollout_buffer: Vec::new(),
+        }
+    }
+
+    // The iterations of the budget are the number of sampled enemy setups.
+    pub fn with_budget(mut self, budget: Budget) -> PimcBot {
+        self.budget = budget;
+        self
+    }
+
+    // Scores the rollouts that don't reach the end of the game.
+    pub fn with_evaluator(mut self, evaluator: Box<dyn Evaluator>) -> PimcBot {
+        self.evaluator = evaluator;
+        self
+    }
+
+    pub fn with_rollouts(
+        mut self,
+        policy: RolloutPolicy,
+        depth: usize,
+        per_action: usize,
+    ) -> PimcBot {
+        self.rollout_policy = policy;
+        self.rollout_depth = depth;
+        self.rollouts_per_action = per_action;
+        self
+    }
+}
+
+impl Bot for PimcBot {
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+        heuristic_placement(&mut self.rng, &ruleset.armies[0], &ruleset.board)
+    }
+
+    fn get_action(&mut self, state: State, _beliefs: &Beliefs) -> Action {
+        let start = Instant::now();
+
+        self.action_buffer.clear();
+        all_possible_moves(&state, &mut self.action_buffer);
+
+        if self.action_buffer.len() == 1 {
+            return self.action_buffer[0];
+        }
+
+        let mut scores = vec![0.0; self.action_buffer.len()];
+        let mut samples = 0;
+
+        while !self.budget.is_spent(samples, start) {
+            let Ok(sample) = self.determinizer.sample(&state, 1) else {
+                break;
+            };
+
+            for (action, score) in self.action_buffer.iter().zip(scores.iter_mut()) {
+                let mut after = sample;
+                let Ok(undo) = after.apply_action(action) else {
+                    continue;
+                };
+
+                if captures_flag(&undo.turn) {
+                    *score += self.rollouts_per_action as f32;
+                    continue;
+                }
+
+                for _ in 0..self.rollouts_per_action {
+                    *score += rollout(
+                        &mut self.rng,
+                        after,
+                        self.rollout_policy,
+                        self.rollout_depth,
+                        self.evaluator.as_ref(),
+                        &mut self.rollout_buffer,
+                    );
+                }
+            }
+
+            samples += 1;
+        }
+
+        if samples == 0 {
+            return *pick_randomly(&mut self.rng, &self.action_buffer);
+        }
+
+        let best = scores
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(idx, _)| idx)
+            .unwrap_or(0);
+
+        self.action_buffer[best]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{assert_beats_simple_bots, assert_plays_legally};
+
+    fn bot(iterations: usize) -> impl Fn(u64) -> Box<dyn Bot> {
+        let budget = Budget {
+            iterations,
+            time: None,
+        };
+        move |seed| Box::new(PimcBot::new(seed).with_budget(budget))
+    }
+
+    #[test]
+    fn test_only_makes_legal_actions() {
+        assert_plays_legally(&bot(4));
+    }
+
+    // Too slow for a debug build, run it with `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_beats_the_simple_bots() {
+        assert_beats_simple_bots(&bot(32), 20, [0.7, 0.9]);
+    }
+}
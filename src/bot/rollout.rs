@@ -0,0 +1,95 @@
+use crate::bot::Evaluator;
+use crate::game::logic::all_possible_moves;
+use crate::game::{Action, Battle, Rank, State, Turn};
+use rand::{seq::SliceRandom, Rng};
+
+// How the moves of both players are picked when a game is played out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RolloutPolicy {
+    // Every possible move is equally likely.
+    Random,
+    // Takes a battle that it wins whenever there is one, otherwise moves randomly.
+    GreedyCaptures,
+}
+
+impl RolloutPolicy {
+    pub fn pick<R: Rng>(&self, rng: &mut R, state: &State, actions: &[Action]) -> Action {
+        if *self == RolloutPolicy::GreedyCaptures {
+            let id = state.current_player_id;
+            let other_id = (id + 1) % 2;
+
+            let winning_battles = actions
+                .iter()
+                .filter(|action| {
+                    state.resolve_battle(action).is_ok_and(|battle| {
+                        battle.is_some_and(|Battle { has_died, .. }| {
+                            has_died[other_id] && !has_died[id]
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            if let Some(action) = winning_battles.choose(rng) {
+                return **action;
+            }
+        }
+
+        *actions.choose(rng).expect("There is a possible move")
+    }
+}
+
+// Plays the game on for at most `max_depth` moves and scores the result for player 0, from -1 for
+// a loss to 1 for a win. Games that haven't ended by then are scored by the evaluator.
+// All ranks of the state have to be known.
+pub fn rollout<R: Rng>(
+    rng: &mut R,
+    mut state: State,
+    policy: RolloutPolicy,
+    max_depth: usize,
+    evaluator: &dyn Evaluator,
+    action_buffer: &mut Vec<Action>,
+) -> f32 {
+    for _ in 0..max_depth {
+        let id = state.current_player_id;
+
+        action_buffer.clear();
+        all_possible_moves(&state, action_buffer);
+
+        if action_buffer.is_empty() {
+            if state.ruleset.end.immobile_player_loses {
+                return win_score(1 - id);
+            }
+
+            state.pass_turn();
+            continue;
+        }
+
+        let action = policy.pick(rng, &state, action_buffer);
+
+        let Ok(undo) = state.apply_action(&action) else {
+            break;
+        };
+
+        if captures_flag(&undo.turn) {
+            return win_score(id);
+        }
+    }
+
+    evaluator.normalized(&state, None)
+}
+
+pub fn captures_flag(turn: &Turn) -> bool {
+    let other_id = (turn.player_id + 1) % 2;
+
+    turn.battle.is_some_and(|Battle { ranks, has_died }| {
+        ranks[other_id] == Rank::Flag && has_died[other_id]
+    })
+}
+
+pub fn win_score(winner: usize) -> f32 {
+    if winner == 0 {
+        1.0
+    } else {
+        -1.0
+    }
+}
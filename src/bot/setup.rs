@@ -0,0 +1,233 @@
+use crate::bot::{Bot, GameResult};
+use crate::game::{Action, Army, Beliefs, Board, Position, Rank, Ruleset, State};
+use rand::Rng;
+
+// The order in which the ranks are placed. The flag goes first so that the bombs can surround it,
+// and the general goes before the spy so that the spy can stay close to it.
+const PLACEMENT_ORDER: [Rank; 12] = [
+    Rank::Flag,
+    Rank::Bomb,
+    Rank::Marshal,
+    Rank::General,
+    Rank::Colonel,
+    Rank::Spy,
+    Rank::Scout,
+    Rank::Miner,
+    Rank::Major,
+    Rank::Captain,
+    Rank::Lieutenant,
+    Rank::Sergeant,
+];
+
+// The board is split into this many lanes, which the strongest pieces are spread over.
+const LANES: u8 = 3;
+
+// Generates plausible deployments for player 0: the flag in the back rows and often surrounded by
+// bombs, scouts and the weaker pieces up front, miners held back, and the strongest pieces spread
+// over the lanes with the spy close to the general.
+//
+// Every piece scores each free square, and a square is picked with a chance that grows
+// exponentially with its score. The randomness is the temperature of that choice: at 0 the best
+// squares are always picked, and the higher it is the closer the setups get to `random_placement`.
+#[derive(Debug, Clone, Copy)]
+pub struct SetupGenerator {
+    randomness: f32,
+}
+
+impl Default for SetupGenerator {
+    fn default() -> Self {
+        SetupGenerator { randomness: 0.5 }
+    }
+}
+
+impl SetupGenerator {
+    pub fn new() -> SetupGenerator {
+        SetupGenerator::default()
+    }
+
+    pub fn with_randomness(mut self, randomness: f32) -> SetupGenerator {
+        self.randomness = randomness.max(0.0);
+        self
+    }
+
+    pub fn generate<R: Rng>(
+        &self,
+        rng: &mut R,
+        army: &Army,
+        board: &Board,
+    ) -> Vec<(Rank, Position)> {
+        let mut free = board.deployment_zone().collect::<Vec<_>>();
+        let mut placements = Vec::with_capacity(army.len());
+
+        for rank in PLACEMENT_ORDER {
+            for _ in 0..army.count(rank) {
+                if free.is_empty() {
+                    return placements;
+                }
+
+                let scores = free
+                    .iter()
+                    .map(|pos| score(rank, pos, &placements, board))
+                    .collect::<Vec<_>>();
+
+                let idx = self.pick(rng, &scores);
+                placements.push((rank, free.swap_remove(idx)));
+            }
+        }
+
+        placements
+    }
+
+    fn pick<R: Rng>(&self, rng: &mut R, scores: &[f32]) -> usize {
+        let best = scores.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+        let weights = scores
+            .iter()
+            .map(|&score| match self.randomness {
+                randomness if randomness > 0.0 => ((score - best) / randomness).exp(),
+                _ => (score == best) as u8 as f32,
+            })
+            .collect::<Vec<_>>();
+
+        let mut target = rng.gen_range(0.0..weights.iter().sum::<f32>());
+        for (idx, weight) in weights.iter().enumerate() {
+            target -= weight;
+            if target < 0.0 {
+                return idx;
+            }
+        }
+
+        weights.len() - 1
+    }
+}
+
+// A deployment from the default `SetupGenerator`.
+pub fn heuristic_placement<R: Rng>(
+    rng: &mut R,
+    army: &Army,
+    board: &Board,
+) -> Vec<(Rank, Position)> {
+    SetupGenerator::default().generate(rng, army, board)
+}
+
+// Deploys the same placement every game, and leaves the actions to another bot.
+pub struct FixedSetupBot {
+    placements: Vec<(Rank, Position)>,
+    bot: Box<dyn Bot>,
+}
+
+impl FixedSetupBot {
+    pub fn new(placements: Vec<(Rank, Position)>, bot: Box<dyn Bot>) -> FixedSetupBot {
+        FixedSetupBot { placements, bot }
+    }
+}
+
+impl Bot for FixedSetupBot {
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+        // The other bot still gets to see the start of the game, in case it keeps state.
+        self.bot.get_initial_placements(ruleset);
+        self.placements.clone()
+    }
+
+    fn get_action(&mut self, state: State, beliefs: &Beliefs) -> Action {
+        self.bot.get_action(state, beliefs)
+    }
+
+    fn offers_draw(&mut self, state: State) -> bool {
+        self.bot.offers_draw(state)
+    }
+
+    fn accepts_draw(&mut self, state: State) -> bool {
+        self.bot.accepts_draw(state)
+    }
+
+    fn on_game_end(&mut self, result: GameResult) {
+        self.bot.on_game_end(result)
+    }
+}
+
+// How much the piece wants to stand on the square, given the pieces that have already been placed.
+fn score(rank: Rank, pos: &Position, placements: &[(Rank, Position)], board: &Board) -> f32 {
+    // 0 on the back row and 1 on the front row.
+    let front = match board.deployment_rows {
+        0 | 1 => 0.0,
+        rows => pos.y as f32 / (rows - 1) as f32,
+    };
+    let back = 1.0 - front;
+
+    let lane = |pos: &Position| pos.x * LANES / board.width.max(1);
+    let is_near = |other: Rank, distance: u8| {
+        placements.iter().any(|(rank, other_pos)| {
+            *rank == other && pos.manhattan_distance(other_pos) <= distance
+        })
+    };
+
+    match rank {
+        Rank::Flag => 4.0 * back,
+        Rank::Bomb if is_near(Rank::Flag, 1) => 3.0,
+        Rank::Bomb => back,
+        Rank::Marshal | Rank::General | Rank::Colonel => {
+            let lane_is_taken = placements.iter().any(|(other, other_pos)| {
+                matches!(other, Rank::Marshal | Rank::General | Rank::Colonel)
+                    && lane(other_pos) == lane(pos)
+            });
+
+            2.0 * !lane_is_taken as u8 as f32 + 1.0 - (front - 0.6).abs()
+        }
+        Rank::Spy if is_near(Rank::General, 2) => 2.0 + back,
+        Rank::Spy => back,
+        Rank::Scout => 3.0 * front,
+        Rank::Miner => 2.0 * back,
+        _ => front,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{validate_placement, Ruleset};
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    #[test]
+    fn test_generates_valid_placements_with_the_flag_in_the_back() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+
+        for ruleset in [Ruleset::classic(), Ruleset::barrage(), Ruleset::duel()] {
+            for randomness in [0.0, 0.5, 2.0] {
+                let generator = SetupGenerator::new().with_randomness(randomness);
+                let placement = generator.generate(&mut rng, &ruleset.armies[0], &ruleset.board);
+                validate_placement(&placement, &ruleset.armies[0], &ruleset.board, 0).unwrap();
+
+                if randomness == 0.0 {
+                    let flag = placement.iter().find(|(rank, _)| *rank == Rank::Flag);
+                    assert_eq!(flag.unwrap().1.y, 0);
+                }
+            }
+        }
+
+        // Without randomness the bombs go around the flag.
+        let ruleset = Ruleset::classic();
+        let placement = SetupGenerator::new().with_randomness(0.0).generate(
+            &mut rng,
+            &ruleset.armies[0],
+            &ruleset.board,
+        );
+        let flag = placement
+            .iter()
+            .find(|(rank, _)| *rank == Rank::Flag)
+            .unwrap()
+            .1;
+        let guards = placement
+            .iter()
+            .filter(|(rank, pos)| *rank == Rank::Bomb && pos.manhattan_distance(&flag) == 1)
+            .count();
+        assert_eq!(
+            guards,
+            flag.neighbours()
+                .iter()
+                .filter(|pos| pos.is_valid_map_position(&ruleset.board))
+                .count()
+        );
+    }
+}
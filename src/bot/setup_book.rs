@@ -0,0 +1,279 @@
+use crate::bot::{Bot, GameResult};
+use crate::game::{validate_placement, Action, Beliefs, Position, Rank, Ruleset, State};
+use anyhow::{Context, Result};
+use rand::{seq::IteratorRandom, Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+// The results of a setup against one opponent.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SetupRecord {
+    pub games: usize,
+    pub wins: usize,
+    pub losses: usize,
+}
+
+impl SetupRecord {
+    pub fn draws(&self) -> usize {
+        self.games.saturating_sub(self.wins + self.losses)
+    }
+
+    // Draws count as half a win.
+    pub fn score(&self) -> f32 {
+        if self.games == 0 {
+            return 0.0;
+        }
+
+        (self.wins as f32 + 0.5 * self.draws() as f32) / self.games as f32
+    }
+
+    pub fn add(&mut self, result: GameResult) {
+        self.games += 1;
+        match result {
+            GameResult::Win => self.wins += 1,
+            GameResult::Loss => self.losses += 1,
+            GameResult::Draw => {}
+        }
+    }
+}
+
+// A deployment for player 0, and how it did against each opponent by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BookSetup {
+    pub name: String,
+    pub placements: Vec<(Rank, Position)>,
+    #[serde(default)]
+    pub records: BTreeMap<String, SetupRecord>,
+}
+
+// A collection of named setups, stored as JSON, with the results they had so far.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SetupBook {
+    pub setups: Vec<BookSetup>,
+}
+
+impl SetupBook {
+    pub fn from_file(path: &Path) -> Result<SetupBook> {
+        let file = std::fs::read_to_string(path)
+            .with_context(|| format!("Can't read the setup book {}", path.display()))?;
+
+        serde_json::from_str(&file)
+            .with_context(|| format!("Invalid setup book in {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+
+        std::fs::write(path, json)
+            .with_context(|| format!("Can't write the setup book {}", path.display()))
+    }
+
+    pub fn add(&mut self, name: &str, placements: Vec<(Rank, Position)>) {
+        self.setups.push(BookSetup {
+            name: name.to_string(),
+            placements,
+            records: BTreeMap::new(),
+        });
+    }
+
+    // Picks the setup to play against the opponent with UCB1: every setup that fits the ruleset
+    // is tried once, and after that the setups with a good score or few games are preferred.
+    pub fn select<R: Rng>(
+        &self,
+        rng: &mut R,
+        opponent: &str,
+        ruleset: &Ruleset,
+        exploration: f32,
+    ) -> Option<usize> {
+        let playable = self
+            .setups
+            .iter()
+            .enumerate()
+            .filter(|(_, setup)| {
+                validate_placement(&setup.placements, &ruleset.armies[0], &ruleset.board, 0).is_ok()
+            })
+            .map(|(idx, setup)| {
+                let record = setup.records.get(opponent).copied().unwrap_or_default();
+                (idx, record)
+            })
+            .collect::<Vec<_>>();
+
+        let untried = playable
+            .iter()
+            .filter(|(_, record)| record.games == 0)
+            .map(|(idx, _)| *idx)
+            .choose(rng);
+        if untried.is_some() {
+            return untried;
+        }
+
+        let total = playable
+            .iter()
+            .map(|(_, record)| record.games)
+            .sum::<usize>() as f32;
+
+        playable
+            .iter()
+            .map(|(idx, record)| {
+                let bonus = exploration * (total.ln() / record.games as f32).sqrt();
+                (*idx, record.score() + bonus)
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(idx, _)| idx)
+    }
+
+    pub fn record(&mut self, idx: usize, opponent: &str, result: GameResult) {
+        if let Some(setup) = self.setups.get_mut(idx) {
+            setup
+                .records
+                .entry(opponent.to_string())
+                .or_default()
+                .add(result);
+        }
+    }
+}
+
+// Deploys a setup from the book and leaves the actions to another bot. The result of every game is
+// added to the book, which is saved again when it was read from a file. When no setup in the book
+// fits the ruleset, the other bot's placement is used.
+pub struct SetupBookBot {
+    book: SetupBook,
+    path: Option<PathBuf>,
+    opponent: String,
+    exploration: f32,
+    rng: Xoshiro256StarStar,
+    bot: Box<dyn Bot>,
+    selected: Option<usize>,
+}
+
+impl SetupBookBot {
+    pub fn new(book: SetupBook, opponent: &str, bot: Box<dyn Bot>, seed: u64) -> SetupBookBot {
+        SetupBookBot {
+            book,
+            path: None,
+            opponent: opponent.to_string(),
+            exploration: 0.5,
+            rng: Xoshiro256StarStar::seed_from_u64(seed),
+            bot,
+            selected: None,
+        }
+    }
+
+    // Keeps the book up to date in the file after every game.
+    pub fn from_file(
+        path: &Path,
+        opponent: &str,
+        bot: Box<dyn Bot>,
+        seed: u64,
+    ) -> Result<SetupBookBot> {
+        let mut setup_book_bot =
+            SetupBookBot::new(SetupBook::from_file(path)?, opponent, bot, seed);
+        setup_book_bot.path = Some(path.to_path_buf());
+        Ok(setup_book_bot)
+    }
+
+    pub fn with_exploration(mut self, exploration: f32) -> SetupBookBot {
+        self.exploration = exploration;
+        self
+    }
+
+    pub fn book(&self) -> &SetupBook {
+        &self.book
+    }
+}
+
+impl Bot for SetupBookBot {
+    fn get_initial_placements(&mut self, ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+        let placements = self.bot.get_initial_placements(ruleset);
+
+        self.selected = self
+            .book
+            .select(&mut self.rng, &self.opponent, ruleset, self.exploration);
+
+        match self.selected {
+            Some(idx) => self.book.setups[idx].placements.clone(),
+            None => placements,
+        }
+    }
+
+    fn get_action(&mut self, state: State, beliefs: &Beliefs) -> Action {
+        self.bot.get_action(state, beliefs)
+    }
+
+    fn offers_draw(&mut self, state: State) -> bool {
+        self.bot.offers_draw(state)
+    }
+
+    fn accepts_draw(&mut self, state: State) -> bool {
+        self.bot.accepts_draw(state)
+    }
+
+    fn on_game_end(&mut self, result: GameResult) {
+        self.bot.on_game_end(result);
+
+        let Some(idx) = self.selected.take() else {
+            return;
+        };
+        self.book.record(idx, &self.opponent, result);
+
+        // Stdout belongs to the server protocol, and a lost result isn't worth losing a game over.
+        if let Some(path) = &self.path {
+            if let Err(error) = self.book.save(path) {
+                eprintln!("{error:#}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::{heuristic_placement, random_placement, RandoBot};
+    use crate::game_coordinator::GameCoordinator;
+
+    #[test]
+    fn test_results_are_recorded_and_saved() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let ruleset = Ruleset::duel();
+        let (army, board) = (&ruleset.armies[0], &ruleset.board);
+
+        let mut book = SetupBook::default();
+        book.add("heuristic", heuristic_placement(&mut rng, army, board));
+        book.add("random", random_placement(&mut rng, army, board));
+        book.add(
+            "barrage",
+            heuristic_placement(&mut rng, &Ruleset::barrage().armies[0], board),
+        );
+
+        let path = std::env::temp_dir().join(format!("setup-book-{}.json", std::process::id()));
+        book.save(&path).unwrap();
+
+        for seed in 0..6 {
+            let bot = SetupBookBot::from_file(&path, "rando", Box::new(RandoBot::new(seed)), seed);
+            let mut game_coordinator = GameCoordinator::new(
+                Box::new(bot.unwrap()),
+                Box::new(RandoBot::new(seed + 100)),
+                ruleset,
+                300,
+            )
+            .unwrap();
+            game_coordinator.play().unwrap();
+        }
+
+        let book = SetupBook::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let games = book
+            .setups
+            .iter()
+            .map(|setup| setup.records.get("rando").map_or(0, |record| record.games))
+            .collect::<Vec<_>>();
+
+        // The setup for another army never gets picked, and the others are both tried.
+        assert_eq!(games.iter().sum::<usize>(), 6);
+        assert!(games[0] > 0 && games[1] > 0);
+        assert_eq!(games[2], 0);
+    }
+}
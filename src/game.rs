@@ -1,12 +1,29 @@
 pub mod action;
+pub mod army;
+pub mod beliefs;
+pub mod board;
+pub mod determinization;
+pub mod error;
 pub mod logic;
+pub mod placement;
 pub mod position;
 pub mod rank;
+pub mod repetition;
+pub mod ruleset;
 pub mod state;
+pub mod zobrist;
 
 pub use action::*;
+pub use army::*;
+pub use beliefs::*;
+pub use board::*;
+pub use determinization::*;
+pub use error::*;
+pub use placement::*;
 pub use position::*;
 pub use rank::*;
+pub use repetition::*;
+pub use ruleset::*;
 pub use state::*;
 
 const ALL_DIRECTION_STEPPERS: [fn(&Position) -> Position; 4] = [
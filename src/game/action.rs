@@ -1,8 +1,8 @@
-use crate::game::{Direction, Position};
+use crate::game::{Board, Direction, Position};
 use crate::json_runner::MoveCommandJson;
 use std::cmp::Ordering;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Action {
     pub from: Position,
     pub to: Position,
@@ -22,10 +22,10 @@ impl Action {
         self.from.x.abs_diff(self.to.x) as usize + self.from.y.abs_diff(self.to.y) as usize
     }
 
-    pub fn reversed(&self) -> Action {
+    pub fn reversed(&self, board: &Board) -> Action {
         Action {
-            from: self.from.reversed(),
-            to: self.to.reversed(),
+            from: self.from.reversed(board),
+            to: self.to.reversed(board),
         }
     }
 }
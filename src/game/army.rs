@@ -0,0 +1,92 @@
+use crate::game::{Rank, ALL_RANKS, RANK_COUNT};
+
+// The largest army a single player can field. This is the size of the classic army, which fills
+// all 40 squares of a player's side of the board.
+pub const MAX_PIECES: usize = 40;
+
+// How many pieces of each rank a player starts with. The two players don't need to have the same
+// army, which makes it possible to play handicap games.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Army {
+    counts: [u8; RANK_COUNT],
+}
+
+impl Army {
+    pub fn new(counts: [u8; RANK_COUNT]) -> Army {
+        let army = Army { counts };
+        assert!(
+            army.len() <= MAX_PIECES,
+            "Army has more than {MAX_PIECES} pieces"
+        );
+        army
+    }
+
+    pub fn from_ranks(ranks: &[Rank]) -> Army {
+        let mut counts = [0; RANK_COUNT];
+        for rank in ranks {
+            counts[*rank as usize] += 1;
+        }
+
+        Army::new(counts)
+    }
+
+    // The full army of the original game.
+    pub fn classic() -> Army {
+        use Rank::*;
+
+        Army::from_ranks(&[
+            Spy, Scout, Scout, Scout, Scout, Scout, Scout, Scout, Scout, Miner, Miner, Miner,
+            Miner, Miner, Sergeant, Sergeant, Sergeant, Sergeant, Lieutenant, Lieutenant,
+            Lieutenant, Lieutenant, Captain, Captain, Captain, Captain, Major, Major, Major,
+            Colonel, Colonel, General, Marshal, Bomb, Bomb, Bomb, Bomb, Bomb, Bomb, Flag,
+        ])
+    }
+
+    // The reduced eight piece army.
+    pub fn barrage() -> Army {
+        use Rank::*;
+
+        Army::from_ranks(&[Spy, Scout, Scout, Miner, General, Marshal, Bomb, Flag])
+    }
+
+    pub fn duel() -> Army {
+        use Rank::*;
+
+        Army::from_ranks(&[
+            Spy, Scout, Scout, Miner, Miner, General, Marshal, Bomb, Bomb, Flag,
+        ])
+    }
+
+    pub fn ultimate_lightning() -> Army {
+        use Rank::*;
+
+        Army::from_ranks(&[
+            Spy, Scout, Scout, Scout, Scout, Miner, Miner, Sergeant, Sergeant, Lieutenant,
+            Lieutenant, Captain, Captain, Major, Colonel, General, Marshal, Bomb, Bomb, Flag,
+        ])
+    }
+
+    pub fn count(&self, rank: Rank) -> usize {
+        self.counts[rank as usize] as usize
+    }
+
+    pub fn with_count(mut self, rank: Rank, count: u8) -> Army {
+        self.counts[rank as usize] = count;
+        Army::new(self.counts)
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.iter().map(|&n| n as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Every piece of the army, sorted by rank.
+    pub fn ranks(&self) -> impl Iterator<Item = Rank> + '_ {
+        ALL_RANKS
+            .iter()
+            .flat_map(|&rank| std::iter::repeat_n(rank, self.count(rank)))
+    }
+}
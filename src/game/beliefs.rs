@@ -0,0 +1,179 @@
+use crate::game::{Army, Piece, Rank, State, ALL_RANKS, MAX_PIECES, RANK_COUNT};
+
+// What can be inferred about the ranks of the pieces of one player, from everything both players
+// have seen: which pieces have moved, which have been revealed by scout moves or battles, and which
+// have been captured. The probabilities assume that every assignment of the hidden ranks that
+// agrees with those observations is equally likely.
+#[derive(Debug, Clone, Copy)]
+pub struct Beliefs {
+    // The player whose pieces the beliefs are about.
+    pub player_id: usize,
+    // The ranks of the pieces that haven't been revealed yet.
+    pub hidden: Army,
+    // Indexed by piece id, ranks are used as indices of the distributions.
+    distributions: [[f32; RANK_COUNT]; MAX_PIECES],
+}
+
+impl Beliefs {
+    pub fn new(state: &State, player_id: usize) -> Beliefs {
+        let pieces = &state.pieces[player_id];
+
+        // Captured pieces and pieces that were revealed on the board are no longer hidden.
+        let mut counts = [0u8; RANK_COUNT];
+        for rank in ALL_RANKS {
+            counts[rank as usize] = state.ruleset.armies[player_id].count(rank) as u8;
+        }
+
+        let revealed_ranks = pieces
+            .iter()
+            .filter(|piece| piece.is_revealed)
+            .map(|piece| piece.rank)
+            .chain(
+                state.graveyards[player_id]
+                    .iter()
+                    .map(|captured| captured.rank),
+            );
+
+        for rank in revealed_ranks.filter(|rank| *rank != Rank::Unknown) {
+            counts[rank as usize] = counts[rank as usize].saturating_sub(1);
+        }
+
+        let hidden = Army::new(counts);
+
+        // Bombs and flags can only be among the hidden pieces that haven't moved. Every other
+        // hidden rank is spread evenly over the remaining hidden pieces, moved or not.
+        let unmoved = pieces
+            .iter()
+            .filter(|piece| !piece.is_revealed && !piece.has_moved)
+            .count();
+
+        let immoveable = hidden.count(Rank::Bomb) + hidden.count(Rank::Flag);
+        let moveable = hidden.len() - immoveable;
+
+        // The chance that an unmoved piece has one of the moveable ranks.
+        let unmoved_share = ratio(unmoved.saturating_sub(immoveable) as f32, unmoved as f32);
+
+        let mut unmoved_distribution = [0.0; RANK_COUNT];
+        let mut moved_distribution = [0.0; RANK_COUNT];
+
+        for rank in ALL_RANKS {
+            let count = hidden.count(rank) as f32;
+
+            if !rank.is_moveable() {
+                unmoved_distribution[rank as usize] = ratio(count, unmoved as f32);
+            } else {
+                unmoved_distribution[rank as usize] = unmoved_share * ratio(count, moveable as f32);
+                moved_distribution[rank as usize] = ratio(count, moveable as f32);
+            }
+        }
+
+        let mut distributions = [[0.0; RANK_COUNT]; MAX_PIECES];
+
+        for piece in pieces {
+            distributions[piece.id as usize] = if piece.is_revealed {
+                let mut distribution = [0.0; RANK_COUNT];
+                distribution[piece.rank as usize] = 1.0;
+                distribution
+            } else if piece.has_moved {
+                moved_distribution
+            } else {
+                unmoved_distribution
+            };
+        }
+
+        Beliefs {
+            player_id,
+            hidden,
+            distributions,
+        }
+    }
+
+    // The beliefs as seen from the other side of the board, see `State::reversed`. Pieces keep
+    // their ids, so only the player the beliefs are about changes.
+    pub fn reversed(&self) -> Beliefs {
+        Beliefs {
+            player_id: (self.player_id + 1) % 2,
+            ..*self
+        }
+    }
+
+    pub fn distribution(&self, piece: &Piece) -> &[f32; RANK_COUNT] {
+        &self.distributions[piece.id as usize]
+    }
+
+    pub fn probability(&self, piece: &Piece, rank: Rank) -> f32 {
+        self.distributions[piece.id as usize]
+            .get(rank as usize)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    // The rank the piece most likely has.
+    pub fn most_likely_rank(&self, piece: &Piece) -> Rank {
+        ALL_RANKS
+            .into_iter()
+            .max_by(|a, b| {
+                self.probability(piece, *a)
+                    .total_cmp(&self.probability(piece, *b))
+            })
+            .unwrap_or(Rank::Unknown)
+    }
+}
+
+fn ratio(a: f32, b: f32) -> f32 {
+    if b == 0.0 {
+        0.0
+    } else {
+        a / b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{logic::all_possible_moves, Ruleset};
+    use crate::testing::random_start;
+    use rand::{seq::SliceRandom, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    #[test]
+    fn test_beliefs_agree_with_the_hidden_army() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(3);
+        let mut actions = Vec::new();
+        let ruleset = Ruleset::classic();
+
+        let mut state = random_start(&mut rng, ruleset);
+
+        for _ in 0..300 {
+            actions.clear();
+            all_possible_moves(&state, &mut actions);
+            let Some(action) = actions.choose(&mut rng) else {
+                break;
+            };
+            state.apply_action(action).unwrap();
+
+            let obscured = state.obscured_for(0);
+            let beliefs = Beliefs::new(&obscured, 1);
+
+            let mut expected_counts = [0.0; RANK_COUNT];
+            for piece in obscured.pieces[1].iter() {
+                let distribution = beliefs.distribution(piece);
+                assert!((distribution.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+
+                let actual_rank = state.piece_at(1, &piece.pos).unwrap().rank;
+                assert!(beliefs.probability(piece, actual_rank) > 0.0);
+
+                if !piece.is_revealed {
+                    for rank in ALL_RANKS {
+                        expected_counts[rank as usize] += distribution[rank as usize];
+                    }
+                }
+            }
+
+            for rank in ALL_RANKS {
+                let expected = expected_counts[rank as usize];
+                assert!((expected - beliefs.hidden.count(rank) as f32).abs() < 1e-3);
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::boardbitmap::{BoardBitmap, MAX_SQUARES};
+use crate::game::{EngineError, Position};
+use crate::json_runner::TileJson;
+
+// The shape of the board: its size, where the water is, and how many rows each player can deploy
+// their pieces on. Player 0 deploys on the rows with the lowest y-coordinates.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Board {
+    pub width: u8,
+    pub height: u8,
+    pub deployment_rows: u8,
+    water: BoardBitmap,
+}
+
+impl Board {
+    pub fn new(width: u8, height: u8, deployment_rows: u8, water: &[Position]) -> Board {
+        assert!(
+            width as usize * height as usize <= MAX_SQUARES,
+            "Board has more than {MAX_SQUARES} squares"
+        );
+        assert!(
+            2 * deployment_rows <= height,
+            "Deployment zones of the players overlap"
+        );
+
+        let mut board = Board {
+            width,
+            height,
+            deployment_rows,
+            water: BoardBitmap::new(),
+        };
+
+        for pos in water {
+            board.water.set(pos.to_bit_index(&board), true);
+        }
+
+        board
+    }
+
+    // The 10x10 board with two 2x2 lakes in the middle.
+    pub fn standard() -> Board {
+        let lakes = itertools::iproduct!([2, 3, 6, 7], [4, 5])
+            .map(|(x, y)| Position { x, y })
+            .collect::<Vec<_>>();
+
+        Board::new(10, 10, 4, &lakes)
+    }
+
+    pub fn from_tiles(tiles: &[TileJson], deployment_rows: u8) -> Result<Board, EngineError> {
+        let width = tiles.iter().map(|tile| tile.coordinate.x + 1).max();
+        let height = tiles.iter().map(|tile| tile.coordinate.y + 1).max();
+
+        let (width, height) = (width.unwrap_or(0), height.unwrap_or(0));
+        if width as usize * height as usize > MAX_SQUARES || 2 * deployment_rows > height {
+            return Err(EngineError::UnsupportedBoard { width, height });
+        }
+
+        let water = tiles
+            .iter()
+            .filter(|tile| tile.is_water)
+            .map(|tile| tile.coordinate.into())
+            .collect::<Vec<_>>();
+
+        Ok(Board::new(width, height, deployment_rows, &water))
+    }
+
+    pub fn square_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    pub fn is_water(&self, pos: &Position) -> bool {
+        self.water.get(pos.to_bit_index(self))
+    }
+
+    // All positions where player 0 can place their pieces.
+    pub fn deployment_zone(&self) -> impl Iterator<Item = Position> + '_ {
+        itertools::iproduct!((0..self.width), (0..self.deployment_rows))
+            .map(|(x, y)| Position { x, y })
+            .filter(|pos| !self.is_water(pos))
+    }
+
+    pub fn is_in_deployment_zone(&self, pos: &Position, player_id: usize) -> bool {
+        let pos = if player_id == 1 {
+            pos.reversed(self)
+        } else {
+            *pos
+        };
+
+        pos.is_valid_map_position(self) && pos.y < self.deployment_rows
+    }
+}
@@ -0,0 +1,209 @@
+use crate::game::{Piece, Rank, State, ALL_RANKS, RANK_COUNT};
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DeterminizationError {
+    #[error("Player {player_id} has {pieces} hidden pieces, but {ranks} hidden ranks")]
+    WrongPieceCount {
+        player_id: usize,
+        pieces: usize,
+        ranks: usize,
+    },
+    #[error("Player {player_id} has more hidden bombs and flags than pieces that haven't moved")]
+    TooFewUnmovedPieces { player_id: usize },
+}
+
+// Fills in the hidden ranks of a player, so that the state can be played out as if everything was
+// known. The sampled ranks always agree with the army of the player and with what has been
+// observed: pieces that have moved are never bombs or flags.
+pub struct Determinizer {
+    rng: Xoshiro256StarStar,
+}
+
+impl Determinizer {
+    pub fn new(seed: u64) -> Determinizer {
+        Determinizer {
+            rng: Xoshiro256StarStar::seed_from_u64(seed),
+        }
+    }
+
+    // Every consistent assignment of the hidden ranks is equally likely.
+    pub fn sample(
+        &mut self,
+        state: &State,
+        player_id: usize,
+    ) -> Result<State, DeterminizationError> {
+        self.sample_weighted(state, player_id, |_, _| 1.0)
+    }
+
+    // The ranks are picked piece by piece, with a chance proportional to how many pieces of the
+    // rank are left to hand out times `prior(piece, rank)`. A prior of zero rules the rank out
+    // for the piece, unless no rank is left that the prior allows.
+    pub fn sample_weighted<F: Fn(&Piece, Rank) -> f32>(
+        &mut self,
+        state: &State,
+        player_id: usize,
+        prior: F,
+    ) -> Result<State, DeterminizationError> {
+        let mut state = *state;
+        let mut remaining = hidden_rank_counts(&state, player_id);
+
+        // The moved pieces go first, so that they can't take the moveable ranks that the
+        // unmoved pieces would need once they run out of bombs and flags.
+        let mut hidden = state.pieces[player_id]
+            .iter()
+            .enumerate()
+            .filter(|(_, piece)| piece.rank == Rank::Unknown)
+            .map(|(idx, piece)| (!piece.has_moved, idx))
+            .collect::<Vec<_>>();
+        hidden.sort_unstable();
+
+        let ranks = remaining.iter().sum::<usize>();
+        if hidden.len() != ranks {
+            return Err(DeterminizationError::WrongPieceCount {
+                player_id,
+                pieces: hidden.len(),
+                ranks,
+            });
+        }
+
+        let unmoved = hidden.iter().filter(|(is_unmoved, _)| *is_unmoved).count();
+        let immoveable = remaining[Rank::Bomb as usize] + remaining[Rank::Flag as usize];
+        if immoveable > unmoved {
+            return Err(DeterminizationError::TooFewUnmovedPieces { player_id });
+        }
+
+        for (_, idx) in hidden {
+            let piece = state.pieces[player_id][idx];
+
+            let mut weights = [0.0; RANK_COUNT];
+            for rank in ALL_RANKS {
+                if remaining[rank as usize] > 0 && (!piece.has_moved || rank.is_moveable()) {
+                    weights[rank as usize] = remaining[rank as usize] as f32 * prior(&piece, rank);
+                }
+            }
+
+            if weights.iter().sum::<f32>() <= 0.0 {
+                for rank in ALL_RANKS {
+                    if !piece.has_moved || rank.is_moveable() {
+                        weights[rank as usize] = remaining[rank as usize] as f32;
+                    }
+                }
+            }
+
+            let rank = self.pick_weighted(&weights);
+            remaining[rank as usize] -= 1;
+            state.pieces[player_id][idx].rank = rank;
+        }
+
+        state.hash = state.compute_hash();
+        Ok(state)
+    }
+
+    fn pick_weighted(&mut self, weights: &[f32; RANK_COUNT]) -> Rank {
+        let total = weights.iter().sum::<f32>();
+        let mut target = self.rng.gen_range(0.0..total);
+
+        let mut picked = Rank::Unknown;
+        for rank in ALL_RANKS
+            .into_iter()
+            .filter(|rank| weights[*rank as usize] > 0.0)
+        {
+            picked = rank;
+            target -= weights[rank as usize];
+            if target < 0.0 {
+                break;
+            }
+        }
+
+        picked
+    }
+}
+
+// How many pieces of each rank the player has whose rank isn't known: the army without the
+// captured pieces and the pieces with a known rank.
+fn hidden_rank_counts(state: &State, player_id: usize) -> [usize; RANK_COUNT] {
+    let mut counts = [0; RANK_COUNT];
+    for rank in ALL_RANKS {
+        counts[rank as usize] = state.ruleset.armies[player_id].count(rank);
+    }
+
+    let known_ranks = state.pieces[player_id]
+        .iter()
+        .map(|piece| piece.rank)
+        .chain(
+            state.graveyards[player_id]
+                .iter()
+                .map(|captured| captured.rank),
+        );
+
+    for rank in known_ranks.filter(|rank| *rank != Rank::Unknown) {
+        counts[rank as usize] = counts[rank as usize].saturating_sub(1);
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::RandoBot;
+    use crate::game::{logic::all_possible_moves, Army, Ruleset};
+    use crate::game_coordinator::GameCoordinator;
+    use crate::testing::random_start;
+    use rand::seq::SliceRandom;
+
+    #[test]
+    fn test_samples_are_consistent_and_playable() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(4);
+        let mut determinizer = Determinizer::new(5);
+        let mut actions = Vec::new();
+        let ruleset = Ruleset::duel();
+
+        let mut state = random_start(&mut rng, ruleset);
+
+        for _ in 0..40 {
+            actions.clear();
+            all_possible_moves(&state, &mut actions);
+            let action = actions.choose(&mut rng).unwrap();
+            state.apply_action(action).unwrap();
+        }
+
+        let obscured = state.obscured_for(0);
+
+        for _ in 0..100 {
+            let sample = determinizer.sample(&obscured, 1).unwrap();
+            assert_eq!(sample.hash, sample.compute_hash());
+
+            let ranks = sample.pieces[1]
+                .iter()
+                .map(|piece| piece.rank)
+                .chain(sample.graveyards[1].iter().map(|captured| captured.rank))
+                .collect::<Vec<_>>();
+            assert_eq!(Army::from_ranks(&ranks), ruleset.armies[1]);
+
+            for piece in sample.pieces[1].iter() {
+                assert!(!piece.has_moved || piece.rank.is_moveable());
+            }
+        }
+
+        // Ruling out every rank but the flag still gives a consistent sample.
+        let sample = determinizer
+            .sample_weighted(&obscured, 1, |_, rank| (rank == Rank::Flag) as u8 as f32)
+            .unwrap();
+        assert!(sample.pieces[1]
+            .iter()
+            .all(|piece| piece.rank != Rank::Unknown));
+
+        let mut game_coordinator = GameCoordinator::from_state(
+            Box::new(RandoBot::new(6)),
+            Box::new(RandoBot::new(7)),
+            sample,
+            1000,
+        )
+        .unwrap();
+        game_coordinator.play().unwrap();
+    }
+}
@@ -0,0 +1,24 @@
+use crate::game::{Position, Rank};
+use thiserror::Error;
+
+// Errors for turns and game states that don't make sense, most likely because of a malformed
+// message from the server.
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EngineError {
+    #[error("{attacker:?} can't attack, because it can't move")]
+    ImmovableAttacker { attacker: Rank },
+    #[error("The flag doesn't fight battles, it is captured when attacked")]
+    FlagDefender,
+    #[error("Player {player_id} has no piece at {pos:?}")]
+    NoPieceAt { player_id: usize, pos: Position },
+    #[error("There is no player {player_id}")]
+    InvalidPlayer { player_id: usize },
+    #[error("A {width}x{height} board is not supported")]
+    UnsupportedBoard { width: u8, height: u8 },
+    #[error("Game state has no last move")]
+    MissingLastMove,
+    #[error("Player {player_id} has more pieces than fit in a state")]
+    TooManyPieces { player_id: usize },
+    #[error("{pos:?} is not a square a piece can move to")]
+    InvalidSquare { pos: Position },
+}
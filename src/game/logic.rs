@@ -1,40 +1,54 @@
 use crate::{
     boardbitmap::BoardBitmap,
-    game::{Action, Piece, Position, Rank, ALL_DIRECTION_STEPPERS},
+    game::{
+        Action, BattleRules, Board, EngineError, Piece, Position, Rank, State,
+        ALL_DIRECTION_STEPPERS,
+    },
 };
 
-pub fn battle_casualties(defender: &Rank, attacker: &Rank) -> (bool, bool) {
+pub fn battle_casualties(
+    rules: &BattleRules,
+    defender: &Rank,
+    attacker: &Rank,
+) -> Result<(bool, bool), EngineError> {
     use Rank::*;
 
-    match (*defender, *attacker) {
-        (Flag, _) | (_, Bomb | Flag) => panic!(),
-        (Marshal, Spy) | (Bomb, Miner) => (true, false),
-        (Bomb, _) => (false, true),
+    Ok(match (*defender, *attacker) {
+        (_, Bomb | Flag) => {
+            return Err(EngineError::ImmovableAttacker {
+                attacker: *attacker,
+            })
+        }
+        (Flag, _) => return Err(EngineError::FlagDefender),
+        (Marshal, Spy) if rules.spy_defeats_marshal => (true, false),
+        (Bomb, Miner) => (true, false),
+        (Bomb, _) => (!rules.bombs_are_permanent, true),
         (defender, attacker) => {
             let def = defender as u8;
             let atk = attacker as u8;
             (atk >= def, def >= atk)
         }
-    }
+    })
 }
 
 pub fn scout_max_steps_with_stepper<F: Fn(&Position) -> Position>(
     stepper: F,
     pos: &Position,
+    board: &Board,
     friend_bitmap: &BoardBitmap,
     enemy_bitmap: &BoardBitmap,
 ) -> usize {
     let mut steps = 0;
     let mut to = stepper(pos);
 
-    while to.is_valid_map_position() {
-        if friend_bitmap.get(to.to_bit_index()) {
+    while to.is_valid_map_position(board) {
+        if friend_bitmap.get(to.to_bit_index(board)) {
             break;
         }
 
         steps += 1;
 
-        if enemy_bitmap.get(to.to_bit_index()) {
+        if enemy_bitmap.get(to.to_bit_index(board)) {
             break;
         }
 
@@ -44,21 +58,37 @@ pub fn scout_max_steps_with_stepper<F: Fn(&Position) -> Position>(
     steps
 }
 
-pub fn has_a_possible_move(
-    friends: &[Piece],
-    friend_bitmap: &BoardBitmap,
-    enemy_bitmap: &BoardBitmap,
-) -> bool {
-    for Piece { rank, pos, .. } in friends {
+// Whether the action is forbidden because of the moves the current player made before it.
+pub fn is_forbidden_by_history(state: &State, action: &Action) -> bool {
+    let id = state.current_player_id;
+
+    state.streaks[id].is_some_and(|streak| streak.forbids(action))
+        || state.chases[id].is_some_and(|chase| chase.forbids(action, &state.ruleset.board))
+}
+
+pub fn has_a_possible_move(state: &State) -> bool {
+    let id = state.current_player_id;
+    let friend_bitmap = &state.bitmaps[id];
+    let enemy_bitmap = &state.bitmaps[(id + 1) % 2];
+    let board = &state.ruleset.board;
+    let scouts_move_far = state.ruleset.movement.scouts_move_far;
+
+    for Piece { rank, pos, .. } in &state.pieces[id] {
         if !rank.is_moveable() {
             continue;
         }
 
-        if *rank == Rank::Scout {
+        if *rank == Rank::Scout && scouts_move_far {
             for stepper in ALL_DIRECTION_STEPPERS {
-                let steps = scout_max_steps_with_stepper(stepper, pos, friend_bitmap, enemy_bitmap);
-                if steps > 0 {
-                    return true;
+                let steps =
+                    scout_max_steps_with_stepper(stepper, pos, board, friend_bitmap, enemy_bitmap);
+
+                let mut to = *pos;
+                for _ in 0..steps {
+                    to = stepper(&to);
+                    if !is_forbidden_by_history(state, &Action { from: *pos, to }) {
+                        return true;
+                    }
                 }
             }
 
@@ -66,36 +96,50 @@ pub fn has_a_possible_move(
         }
 
         for neighbour in IntoIterator::into_iter(pos.neighbours()) {
-            if !neighbour.is_valid_map_position() || friend_bitmap.get(neighbour.to_bit_index()) {
+            if !neighbour.is_valid_map_position(board)
+                || friend_bitmap.get(neighbour.to_bit_index(board))
+            {
                 continue;
             }
 
-            return true;
+            let action = Action {
+                from: *pos,
+                to: neighbour,
+            };
+
+            if !is_forbidden_by_history(state, &action) {
+                return true;
+            }
         }
     }
 
     false
 }
 
-pub fn all_possible_moves(
-    friends: &[Piece],
-    friend_bitmap: &BoardBitmap,
-    enemy_bitmap: &BoardBitmap,
-    actions: &mut Vec<Action>,
-) {
-    for Piece { rank, pos, .. } in friends {
+pub fn all_possible_moves(state: &State, actions: &mut Vec<Action>) {
+    let id = state.current_player_id;
+    let friend_bitmap = &state.bitmaps[id];
+    let enemy_bitmap = &state.bitmaps[(id + 1) % 2];
+    let board = &state.ruleset.board;
+    let scouts_move_far = state.ruleset.movement.scouts_move_far;
+
+    for Piece { rank, pos, .. } in &state.pieces[id] {
         if !rank.is_moveable() {
             continue;
         }
 
-        if *rank == Rank::Scout {
+        if *rank == Rank::Scout && scouts_move_far {
             for stepper in ALL_DIRECTION_STEPPERS {
-                let steps = scout_max_steps_with_stepper(stepper, pos, friend_bitmap, enemy_bitmap);
+                let steps =
+                    scout_max_steps_with_stepper(stepper, pos, board, friend_bitmap, enemy_bitmap);
 
                 let mut to = *pos;
                 for _ in 0..steps {
                     to = stepper(&to);
-                    actions.push(Action { from: *pos, to });
+                    let action = Action { from: *pos, to };
+                    if !is_forbidden_by_history(state, &action) {
+                        actions.push(action);
+                    }
                 }
             }
 
@@ -103,14 +147,20 @@ pub fn all_possible_moves(
         }
 
         for neighbour in IntoIterator::into_iter(pos.neighbours()) {
-            if !neighbour.is_valid_map_position() || friend_bitmap.get(neighbour.to_bit_index()) {
+            if !neighbour.is_valid_map_position(board)
+                || friend_bitmap.get(neighbour.to_bit_index(board))
+            {
                 continue;
             }
 
-            actions.push(Action {
+            let action = Action {
                 from: *pos,
                 to: neighbour,
-            });
+            };
+
+            if !is_forbidden_by_history(state, &action) {
+                actions.push(action);
+            }
         }
     }
 }
@@ -0,0 +1,122 @@
+use crate::boardbitmap::BoardBitmap;
+use crate::game::{Army, Board, Position, Rank, ALL_RANKS, RANK_COUNT};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PlacementError {
+    #[error("{rank:?} is placed outside the board at {pos:?}")]
+    OutsideBoard { rank: Rank, pos: Position },
+    #[error("{rank:?} is placed in a lake at {pos:?}")]
+    InWater { rank: Rank, pos: Position },
+    #[error("{rank:?} is placed outside the deployment zone at {pos:?}")]
+    OutsideDeploymentZone { rank: Rank, pos: Position },
+    #[error("More than one piece is placed at {pos:?}")]
+    DuplicateSquare { pos: Position },
+    #[error("{rank:?} can't be placed, it is not a piece")]
+    InvalidRank { rank: Rank },
+    #[error("Placed {found} pieces of rank {rank:?}, but the army has {expected}")]
+    WrongCount {
+        rank: Rank,
+        expected: usize,
+        found: usize,
+    },
+}
+
+// Checks that a placement uses exactly the pieces of the army, and that they all stand on
+// different squares of the deployment zone of the player.
+pub fn validate_placement(
+    placement: &[(Rank, Position)],
+    army: &Army,
+    board: &Board,
+    player_id: usize,
+) -> Result<(), PlacementError> {
+    use PlacementError::*;
+
+    let mut occupied = BoardBitmap::new();
+    let mut counts = [0; RANK_COUNT];
+
+    for &(rank, pos) in placement {
+        if rank == Rank::Unknown {
+            return Err(InvalidRank { rank });
+        }
+
+        if pos.x >= board.width || pos.y >= board.height {
+            return Err(OutsideBoard { rank, pos });
+        }
+
+        if board.is_water(&pos) {
+            return Err(InWater { rank, pos });
+        }
+
+        if !board.is_in_deployment_zone(&pos, player_id) {
+            return Err(OutsideDeploymentZone { rank, pos });
+        }
+
+        let idx = pos.to_bit_index(board);
+        if occupied.get(idx) {
+            return Err(DuplicateSquare { pos });
+        }
+        occupied.set(idx, true);
+
+        counts[rank as usize] += 1;
+    }
+
+    for rank in ALL_RANKS {
+        let expected = army.count(rank);
+        let found = counts[rank as usize];
+
+        if expected != found {
+            return Err(WrongCount {
+                rank,
+                expected,
+                found,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::random_placement;
+    use crate::game::Ruleset;
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    #[test]
+    fn test_random_placements_are_valid_and_mistakes_are_caught() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+
+        for ruleset in Ruleset::presets() {
+            let army = &ruleset.armies[0];
+            let board = &ruleset.board;
+            let placement = random_placement(&mut rng, army, board);
+
+            assert_eq!(validate_placement(&placement, army, board, 0), Ok(()));
+
+            let (rank, pos) = placement[0];
+            let mut duplicated = placement.clone();
+            duplicated[1].1 = pos;
+            assert_eq!(
+                validate_placement(&duplicated, army, board, 0),
+                Err(PlacementError::DuplicateSquare { pos })
+            );
+
+            assert_eq!(
+                validate_placement(&placement, army, board, 1),
+                Err(PlacementError::OutsideDeploymentZone { rank, pos })
+            );
+
+            assert_eq!(
+                validate_placement(&placement[1..], army, board, 0),
+                Err(PlacementError::WrongCount {
+                    rank,
+                    expected: army.count(rank),
+                    found: army.count(rank) - 1,
+                })
+            );
+        }
+    }
+}
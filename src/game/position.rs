@@ -1,6 +1,8 @@
+use crate::game::Board;
 use crate::json_runner::PositionJson;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Position {
     pub x: u8,
     pub y: u8,
@@ -39,29 +41,25 @@ impl Position {
         [self.up(), self.right(), self.down(), self.left()]
     }
 
-    pub fn is_valid_map_position(&self) -> bool {
+    pub fn is_valid_map_position(&self, board: &Board) -> bool {
         // Must be inside the board boundaries.
-        if self.x > 9 || self.y > 9 {
+        if self.x >= board.width || self.y >= board.height {
             return false;
         }
 
         // Position cannot be inside a lake.
-        if ((2..4).contains(&self.x) || (6..8).contains(&self.x)) && (4..6).contains(&self.y) {
-            return false;
-        }
-
-        true
+        !board.is_water(self)
     }
 
-    pub fn reversed(&self) -> Position {
+    pub fn reversed(&self, board: &Board) -> Position {
         Position {
-            x: 9 - self.x,
-            y: 9 - self.y,
+            x: board.width - 1 - self.x,
+            y: board.height - 1 - self.y,
         }
     }
 
-    pub fn to_bit_index(self) -> usize {
-        self.x as usize + 10 * self.y as usize
+    pub fn to_bit_index(self, board: &Board) -> usize {
+        self.x as usize + board.width as usize * self.y as usize
     }
 
     pub fn manhattan_distance(&self, other: &Position) -> u8 {
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Deserialize, Serialize)]
 pub enum Rank {
     Spy,
     Scout,
@@ -19,17 +19,28 @@ pub enum Rank {
     Unknown, // Not very idiomatic, but it simplifies some other stuff
 }
 
-pub const STARTING_RANKS: [Rank; 8] = [
+// The number of real ranks, i.e. every rank except `Rank::Unknown`.
+pub const RANK_COUNT: usize = Rank::Unknown as usize;
+
+pub const ALL_RANKS: [Rank; RANK_COUNT] = [
     Rank::Spy,
     Rank::Scout,
-    Rank::Scout,
     Rank::Miner,
+    Rank::Sergeant,
+    Rank::Lieutenant,
+    Rank::Captain,
+    Rank::Major,
+    Rank::Colonel,
     Rank::General,
     Rank::Marshal,
     Rank::Bomb,
     Rank::Flag,
 ];
 
+// How much each rank is worth, indexed by rank. The same scale is used by the evaluation of the bots
+// and to adjudicate games that reach the turn limit.
+pub const MATERIAL_VALUES: [u32; RANK_COUNT] = [3, 2, 3, 2, 3, 4, 5, 6, 8, 10, 3, 0];
+
 impl Rank {
     pub fn is_moveable(&self) -> bool {
         !matches!(&self, Rank::Flag | Rank::Bomb)
@@ -44,6 +55,11 @@ impl TryFrom<&str> for Rank {
             "Spy" => Rank::Spy,
             "Scout" => Rank::Scout,
             "Miner" => Rank::Miner,
+            "Sergeant" => Rank::Sergeant,
+            "Lieutenant" => Rank::Lieutenant,
+            "Captain" => Rank::Captain,
+            "Major" => Rank::Major,
+            "Colonel" => Rank::Colonel,
             "General" => Rank::General,
             "Marshal" => Rank::Marshal,
             "Bomb" => Rank::Bomb,
@@ -0,0 +1,176 @@
+use crate::boardbitmap::BoardBitmap;
+use crate::game::{Action, Board, Position};
+
+// A piece may not move back and forth between the same two squares more than this many times in a
+// row (the ISF two-square rule).
+pub const MAX_MOVES_BETWEEN_SAME_SQUARES: u8 = 3;
+
+// The non-stop back-and-forth movement of the piece a player moved most recently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MoveStreak {
+    pub from: Position,
+    pub to: Position,
+    pub count: u8,
+}
+
+impl MoveStreak {
+    pub fn new(action: &Action) -> MoveStreak {
+        MoveStreak {
+            from: action.from,
+            to: action.to,
+            count: 1,
+        }
+    }
+
+    // An action continues the streak when the same piece moves back towards where it came from,
+    // without passing it. For most pieces that means going back to the exact previous square, but
+    // a scout can also stop anywhere in between.
+    pub fn is_continued_by(&self, action: &Action) -> bool {
+        if action.from != self.to || action.to == self.to {
+            return false;
+        }
+
+        let (lo, hi) = (self.from.min(self.to), self.from.max(self.to));
+
+        if self.from.x == self.to.x {
+            action.to.x == self.from.x && (lo.y..=hi.y).contains(&action.to.y)
+        } else {
+            action.to.y == self.from.y && (lo.x..=hi.x).contains(&action.to.x)
+        }
+    }
+
+    pub fn forbids(&self, action: &Action) -> bool {
+        self.count >= MAX_MOVES_BETWEEN_SAME_SQUARES && self.is_continued_by(action)
+    }
+
+    pub fn after(streak: Option<MoveStreak>, action: &Action) -> MoveStreak {
+        match streak {
+            Some(streak) if streak.is_continued_by(action) => MoveStreak {
+                from: action.from,
+                to: action.to,
+                count: streak.count + 1,
+            },
+            _ => MoveStreak::new(action),
+        }
+    }
+
+    pub fn reversed(&self, board: &Board) -> MoveStreak {
+        MoveStreak {
+            from: self.from.reversed(board),
+            to: self.to.reversed(board),
+            count: self.count,
+        }
+    }
+}
+
+// A continuous chase: a player keeps moving the same piece next to an enemy piece, which keeps
+// fleeing. The chaser may not threaten the chased piece from a square it already visited during
+// the chase (the ISF more-squares rule).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chase {
+    pub chaser: Position,
+    pub chased: Position,
+    pub visited: BoardBitmap,
+}
+
+impl Chase {
+    pub fn new(action: &Action, chased: Position, board: &Board) -> Chase {
+        let mut visited = BoardBitmap::new();
+        visited.set(action.from.to_bit_index(board), true);
+        visited.set(action.to.to_bit_index(board), true);
+
+        Chase {
+            chaser: action.to,
+            chased,
+            visited,
+        }
+    }
+
+    pub fn is_continued_by(&self, action: &Action) -> bool {
+        action.from == self.chaser && action.to.manhattan_distance(&self.chased) == 1
+    }
+
+    pub fn forbids(&self, action: &Action, board: &Board) -> bool {
+        self.is_continued_by(action) && self.visited.get(action.to.to_bit_index(board))
+    }
+
+    pub fn continued(&self, action: &Action, board: &Board) -> Chase {
+        let mut visited = self.visited;
+        visited.set(action.to.to_bit_index(board), true);
+
+        Chase {
+            chaser: action.to,
+            chased: self.chased,
+            visited,
+        }
+    }
+
+    pub fn reversed(&self, board: &Board) -> Chase {
+        Chase {
+            chaser: self.chaser.reversed(board),
+            chased: self.chased.reversed(board),
+            visited: self.visited.reversed(board.square_count()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(from: (u8, u8), to: (u8, u8)) -> Action {
+        Action {
+            from: Position {
+                x: from.0,
+                y: from.1,
+            },
+            to: Position { x: to.0, y: to.1 },
+        }
+    }
+
+    #[test]
+    fn test_fourth_move_between_same_squares_is_forbidden() {
+        let forth = action((0, 0), (0, 1));
+        let back = action((0, 1), (0, 0));
+
+        let mut streak = MoveStreak::new(&forth);
+        assert!(!streak.forbids(&back));
+
+        streak = MoveStreak::after(Some(streak), &back);
+        streak = MoveStreak::after(Some(streak), &forth);
+        assert_eq!(streak.count, 3);
+
+        assert!(streak.forbids(&back));
+        assert!(!streak.forbids(&action((0, 1), (1, 1))));
+    }
+
+    #[test]
+    fn test_scout_cannot_stop_inside_the_range_it_oscillates_over() {
+        let mut streak = MoveStreak::new(&action((0, 0), (0, 3)));
+        streak = MoveStreak::after(Some(streak), &action((0, 3), (0, 1)));
+        streak = MoveStreak::after(Some(streak), &action((0, 1), (0, 3)));
+        assert_eq!(streak.count, 3);
+
+        assert!(streak.forbids(&action((0, 3), (0, 2))));
+        assert!(!streak.forbids(&action((0, 3), (0, 5))));
+    }
+
+    #[test]
+    fn test_chaser_cannot_threaten_from_a_visited_square() {
+        let pos = |x, y| Position { x, y };
+        let board = Board::standard();
+
+        // The chaser moves next to the chased piece, which flees one square to the right.
+        let mut chase = Chase::new(&action((0, 2), (1, 2)), pos(1, 1), &board);
+        chase.chased = pos(2, 1);
+
+        assert!(chase.is_continued_by(&action((1, 2), (2, 2))));
+        chase = chase.continued(&action((1, 2), (2, 2)), &board);
+
+        // The chased piece flees back, and the chaser can only follow it to a new square.
+        chase.chased = pos(1, 1);
+        assert!(chase.forbids(&action((2, 2), (1, 2)), &board));
+        assert!(!chase.forbids(&action((2, 2), (2, 1)), &board));
+        assert!(!chase.forbids(&action((2, 2), (3, 2)), &board));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::game::{Army, Board};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BattleRules {
+    // Whether a spy defeats the marshal when the spy is the attacker.
+    pub spy_defeats_marshal: bool,
+    // Whether a bomb stays on the board after it has blown up an attacker.
+    pub bombs_are_permanent: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MovementRules {
+    // Whether scouts can move any number of empty squares in a straight line.
+    pub scouts_move_far: bool,
+    pub two_square_rule: bool,
+    pub more_squares_rule: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EndConditions {
+    // Whether a player who cannot move any piece loses. Otherwise that player has to pass.
+    pub immobile_player_loses: bool,
+}
+
+// Everything that differs between the variants of the game.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Ruleset {
+    pub name: &'static str,
+    pub board: Board,
+    pub armies: [Army; 2],
+    pub battle: BattleRules,
+    pub movement: MovementRules,
+    pub end: EndConditions,
+}
+
+impl Ruleset {
+    pub fn classic() -> Ruleset {
+        Ruleset {
+            name: "classic",
+            board: Board::standard(),
+            armies: [Army::classic(); 2],
+            battle: BattleRules {
+                spy_defeats_marshal: true,
+                bombs_are_permanent: true,
+            },
+            movement: MovementRules {
+                scouts_move_far: true,
+                two_square_rule: true,
+                more_squares_rule: true,
+            },
+            end: EndConditions {
+                immobile_player_loses: true,
+            },
+        }
+    }
+
+    pub fn barrage() -> Ruleset {
+        Ruleset {
+            name: "barrage",
+            armies: [Army::barrage(); 2],
+            ..Ruleset::classic()
+        }
+    }
+
+    pub fn duel() -> Ruleset {
+        Ruleset {
+            name: "duel",
+            armies: [Army::duel(); 2],
+            ..Ruleset::classic()
+        }
+    }
+
+    pub fn ultimate_lightning() -> Ruleset {
+        Ruleset {
+            name: "ultimate-lightning",
+            armies: [Army::ultimate_lightning(); 2],
+            ..Ruleset::classic()
+        }
+    }
+
+    pub fn presets() -> [Ruleset; 4] {
+        [
+            Ruleset::classic(),
+            Ruleset::barrage(),
+            Ruleset::duel(),
+            Ruleset::ultimate_lightning(),
+        ]
+    }
+
+    pub fn by_name(name: &str) -> Option<Ruleset> {
+        Ruleset::presets()
+            .into_iter()
+            .find(|ruleset| ruleset.name == name)
+    }
+
+    // The same rules from the point of view of the other player.
+    pub fn reversed(&self) -> Ruleset {
+        Ruleset {
+            armies: [self.armies[1], self.armies[0]],
+            ..*self
+        }
+    }
+}
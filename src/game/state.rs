@@ -1,14 +1,22 @@
 use crate::boardbitmap::BoardBitmap;
-use crate::game::logic::scout_max_steps_with_stepper;
-use crate::game::{Action, Position, Rank};
+use crate::game::logic::{battle_casualties, scout_max_steps_with_stepper};
+use crate::game::zobrist::{piece_hash, side_hash};
+use crate::game::{
+    Action, Board, Chase, EngineError, MoveStreak, Position, Rank, Ruleset, MAX_PIECES,
+};
 use crate::json_runner::{BattleResultJson, GameStateJson, TileJson};
+use std::collections::hash_map::DefaultHasher;
 use std::fmt;
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
 use thiserror::Error;
 use tinyvec::ArrayVec;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct Piece {
+    // Assigned at setup and never changed, so a piece can be followed through the game. Ids are
+    // unique among the pieces of one player.
+    pub id: u8,
     pub pos: Position,
     pub rank: Rank,
     pub has_moved: bool,
@@ -18,6 +26,7 @@ pub struct Piece {
 impl Default for Piece {
     fn default() -> Self {
         Piece {
+            id: 0,
             pos: Position { x: 0, y: 0 },
             rank: Rank::Unknown,
             has_moved: false,
@@ -26,7 +35,43 @@ impl Default for Piece {
     }
 }
 
-#[derive(Error, Debug, PartialEq, Eq)]
+impl Piece {
+    // Whether the piece can have the given rank, judging only by what both players have seen.
+    // A piece that has moved can't be a bomb or a flag, and moving more than one square reveals
+    // a scout.
+    pub fn could_be(&self, rank: Rank) -> bool {
+        if self.is_revealed {
+            self.rank == rank
+        } else if self.has_moved {
+            rank.is_moveable()
+        } else {
+            true
+        }
+    }
+}
+
+// A piece that was taken off the board in a battle.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CapturedPiece {
+    pub id: u8,
+    pub rank: Rank,
+    pub turn: usize,
+    // The rank of the piece it fought. Both pieces are captured when they have the same rank.
+    pub captured_by: Rank,
+}
+
+impl Default for CapturedPiece {
+    fn default() -> Self {
+        CapturedPiece {
+            id: 0,
+            rank: Rank::Unknown,
+            turn: 0,
+            captured_by: Rank::Unknown,
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ActionError {
     NoFriendlyPieceOnFromPosition,
     FriendlyPieceIsNotMoveable,
@@ -34,6 +79,8 @@ pub enum ActionError {
     ToPositionIsAnInvalidMapPosition,
     MovementIsNotStraight,
     InvalidMovementDistance,
+    ViolatesTwoSquareRule,
+    ViolatesMoreSquaresRule,
 }
 
 impl Display for ActionError {
@@ -42,13 +89,22 @@ impl Display for ActionError {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct Battle {
     pub ranks: [Rank; 2],
     pub has_died: [bool; 2],
 }
 
-impl From<BattleResultJson> for Battle {
-    fn from(battle: BattleResultJson) -> Battle {
+impl TryFrom<BattleResultJson> for Battle {
+    type Error = EngineError;
+
+    fn try_from(battle: BattleResultJson) -> Result<Battle, EngineError> {
+        for player_id in [battle.attacker.player, battle.defender.player] {
+            if player_id > 1 {
+                return Err(EngineError::InvalidPlayer { player_id });
+            }
+        }
+
         let mut ranks = [battle.attacker.rank, battle.defender.rank];
         ranks.as_mut_slice().swap(0, battle.attacker.player);
 
@@ -56,47 +112,109 @@ impl From<BattleResultJson> for Battle {
             None => [true, true],
             Some(0) => [false, true],
             Some(1) => [true, false],
-            _ => unreachable!(),
+            Some(player_id) => return Err(EngineError::InvalidPlayer { player_id }),
         };
 
-        Battle { ranks, has_died }
+        Ok(Battle { ranks, has_died })
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub struct Turn {
     pub player_id: usize,
     pub action: Action,
     pub battle: Option<Battle>,
+    // The ids of the pieces of each player that took part in the turn. They are filled in by
+    // `State::update_with_turn`, so only the turn in the returned `Undo` has them.
+    pub piece_ids: [Option<u8>; 2],
+}
+
+impl Turn {
+    // The same turn after `State::reversed`.
+    pub fn reversed(&self, board: &Board) -> Turn {
+        Turn {
+            player_id: (self.player_id + 1) % 2,
+            action: self.action.reversed(board),
+            battle: self.battle.map(|Battle { ranks, has_died }| Battle {
+                ranks: [ranks[1], ranks[0]],
+                has_died: [has_died[1], has_died[0]],
+            }),
+            piece_ids: [self.piece_ids[1], self.piece_ids[0]],
+        }
+    }
 }
 
-impl From<GameStateJson> for Turn {
-    fn from(state: GameStateJson) -> Turn {
-        let Some(last_move) = state.last_move else { panic!() };
+impl TryFrom<GameStateJson> for Turn {
+    type Error = EngineError;
+
+    fn try_from(state: GameStateJson) -> Result<Turn, EngineError> {
+        let Some(last_move) = state.last_move else {
+            return Err(EngineError::MissingLastMove);
+        };
+
+        if state.active_player > 1 {
+            return Err(EngineError::InvalidPlayer {
+                player_id: state.active_player,
+            });
+        }
 
         let last_player_id = (state.active_player + 1) % 2;
 
-        Turn {
+        Ok(Turn {
             player_id: last_player_id,
             action: last_move.into(),
-            battle: state.battle_result.map(|x| x.into()),
-        }
+            battle: state.battle_result.map(Battle::try_from).transpose()?,
+            piece_ids: [None; 2],
+        })
     }
 }
 
-#[derive(Clone, Copy)]
+// Everything that is needed to take back a turn with `State::unmake`.
+#[derive(Debug, Clone, Copy)]
+pub struct Undo {
+    pub turn: Turn,
+
+    // The index and the previous value of the piece of each player that took part in the turn,
+    // and whether that piece was removed from the board.
+    changed: [Option<(usize, Piece)>; 2],
+    removed: [bool; 2],
+
+    current_player_id: usize,
+    turn_count: usize,
+    bitmaps: [BoardBitmap; 2],
+    streaks: [Option<MoveStreak>; 2],
+    chases: [Option<Chase>; 2],
+    hash: u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub struct State {
     pub current_player_id: usize,
     pub turn_count: usize,
 
-    pub pieces: [ArrayVec<[Piece; 8]>; 2],
+    pub ruleset: Ruleset,
+    pub pieces: [ArrayVec<[Piece; MAX_PIECES]>; 2],
     pub bitmaps: [BoardBitmap; 2],
+
+    // The pieces each player has lost, in the order they were captured. Captured pieces have
+    // been revealed in battle, so both players know all of them.
+    pub graveyards: [ArrayVec<[CapturedPiece; MAX_PIECES]>; 2],
+
+    pub streaks: [Option<MoveStreak>; 2],
+    pub chases: [Option<Chase>; 2],
+
+    // Zobrist hash of the pieces and the player to move. Repetition state (`streaks` and
+    // `chases`), the turn count and the ruleset are not part of it.
+    pub hash: u64,
 }
 
 impl State {
-    pub fn new_with_placements(placements: &[&[(Rank, Position)]; 2]) -> State {
+    pub fn new_with_placements(ruleset: Ruleset, placements: &[&[(Rank, Position)]; 2]) -> State {
         let pieces: [ArrayVec<_>; 2] = placements.map(|ps| {
             ps.iter()
-                .map(|&(rank, pos)| Piece {
+                .zip(0..)
+                .map(|(&(rank, pos), id)| Piece {
+                    id,
                     pos,
                     rank,
                     has_moved: false,
@@ -109,24 +227,46 @@ impl State {
 
         for (i, pieces) in pieces.iter().enumerate() {
             for Piece { pos, .. } in pieces.iter() {
-                bitmaps[i].set(pos.to_bit_index(), true);
+                bitmaps[i].set(pos.to_bit_index(&ruleset.board), true);
             }
         }
 
-        State {
+        let mut state = State {
             current_player_id: 0,
             turn_count: 0,
+            ruleset,
             pieces,
             bitmaps,
-        }
+            graveyards: [ArrayVec::new(), ArrayVec::new()],
+            streaks: [None; 2],
+            chases: [None; 2],
+            hash: 0,
+        };
+
+        state.hash = state.compute_hash();
+        state
     }
 
-    pub fn new_from_json_state(state: &GameStateJson) -> State {
+    // The board layout sent by the server takes precedence over the one in the ruleset.
+    pub fn new_from_json_state(
+        state: &GameStateJson,
+        ruleset: Ruleset,
+    ) -> Result<State, EngineError> {
+        let ruleset = Ruleset {
+            board: Board::from_tiles(&state.board, ruleset.board.deployment_rows)?,
+            ..ruleset
+        };
+
         let mut res = State {
             current_player_id: 0,
             turn_count: 0,
+            ruleset,
             pieces: [ArrayVec::new(), ArrayVec::new()],
             bitmaps: [BoardBitmap::new(); 2],
+            graveyards: [ArrayVec::new(), ArrayVec::new()],
+            streaks: [None; 2],
+            chases: [None; 2],
+            hash: 0,
         };
 
         for TileJson {
@@ -137,79 +277,360 @@ impl State {
         } in &state.board
         {
             if let Some(id) = owner {
+                if *id > 1 {
+                    return Err(EngineError::InvalidPlayer { player_id: *id });
+                }
+
                 let rank = rank
                     .as_ref()
                     .and_then(|s| Rank::try_from(s.as_str()).ok())
                     .unwrap_or(Rank::Unknown);
 
+                if res.pieces[*id].len() == MAX_PIECES {
+                    return Err(EngineError::TooManyPieces { player_id: *id });
+                }
+
+                // Only the ranks of our own pieces are sent, and the opponent hasn't seen them yet.
                 res.pieces[*id].push(Piece {
+                    id: res.pieces[*id].len() as u8,
                     pos: (*coordinate).into(),
                     rank,
                     has_moved: false,
-                    is_revealed: rank != Rank::Unknown,
+                    is_revealed: false,
                 });
             }
         }
 
         for (i, pieces) in res.pieces.iter().enumerate() {
             for Piece { pos, .. } in pieces.iter() {
-                res.bitmaps[i].set(pos.to_bit_index(), true);
+                res.bitmaps[i].set(pos.to_bit_index(&res.ruleset.board), true);
             }
         }
 
-        res
+        res.hash = res.compute_hash();
+        Ok(res)
     }
 
+    // Computes the hash from scratch, the `hash` field is kept up to date incrementally.
+    pub fn compute_hash(&self) -> u64 {
+        let board = &self.ruleset.board;
+
+        let pieces_hash = [0, 1]
+            .into_iter()
+            .flat_map(|id| {
+                self.pieces[id]
+                    .iter()
+                    .map(move |piece| piece_hash(id, piece, board))
+            })
+            .fold(0, |acc, hash| acc ^ hash);
+
+        pieces_hash ^ side_hash(self.current_player_id)
+    }
+
+    // Identifies the position for repetitions and transpositions. The hash leaves out the move
+    // streaks and chases, but they decide which moves are allowed, so they are part of the key.
+    pub fn position_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash.hash(&mut hasher);
+        self.streaks.hash(&mut hasher);
+
+        for (player_id, chase) in self.chases.iter().enumerate() {
+            let Some(chase) = chase else {
+                continue;
+            };
+
+            (player_id, chase.chaser, chase.chased).hash(&mut hasher);
+            for idx in 0..self.ruleset.board.square_count() {
+                chase.visited.get(idx).hash(&mut hasher);
+            }
+        }
+
+        hasher.finish()
+    }
+
+    // A copy of the state as the given player sees it: the ranks of the opponent's pieces are
+    // hidden, unless they have been revealed.
+    pub fn obscured_for(&self, player_id: usize) -> State {
+        let mut state = *self;
+
+        for Piece {
+            rank, is_revealed, ..
+        } in state.pieces[(player_id + 1) % 2].iter_mut()
+        {
+            if !*is_revealed {
+                *rank = Rank::Unknown;
+            }
+        }
+
+        state.hash = state.compute_hash();
+        state
+    }
+
+    // Hands the turn to the other player without moving.
+    pub fn pass_turn(&mut self) {
+        let next_player_id = (self.current_player_id + 1) % 2;
+
+        self.hash ^= side_hash(self.current_player_id) ^ side_hash(next_player_id);
+        self.current_player_id = next_player_id;
+        self.turn_count += 1;
+    }
+
+    // Mirrors the board and swaps the players, so that the player who was 1 becomes 0.
     pub fn reversed(&self) -> State {
-        State {
-            current_player_id: self.current_player_id,
+        let board = &self.ruleset.board;
+
+        let mut state = State {
+            current_player_id: (self.current_player_id + 1) % 2,
             turn_count: self.turn_count,
+            ruleset: self.ruleset.reversed(),
             pieces: [1, 0].map(|id| {
                 self.pieces[id]
                     .into_iter()
                     .map(|piece @ Piece { pos, .. }| Piece {
-                        pos: pos.reversed(),
+                        pos: pos.reversed(board),
                         ..piece
                     })
                     .collect()
             }),
-            bitmaps: [1, 0].map(|id| self.bitmaps[id].reversed()),
-        }
+            bitmaps: [1, 0].map(|id| self.bitmaps[id].reversed(board.square_count())),
+            graveyards: [1, 0].map(|id| self.graveyards[id]),
+            streaks: [1, 0].map(|id| self.streaks[id].map(|streak| streak.reversed(board))),
+            chases: [1, 0].map(|id| self.chases[id].map(|chase| chase.reversed(board))),
+            hash: 0,
+        };
+
+        state.hash = state.compute_hash();
+        state
+    }
+
+    pub fn piece_at(&self, player_id: usize, pos: &Position) -> Option<&Piece> {
+        self.pieces[player_id]
+            .iter()
+            .find(|piece| piece.pos == *pos)
+    }
+
+    fn piece_index(&self, player_id: usize, pos: &Position) -> Result<usize, EngineError> {
+        self.pieces[player_id]
+            .iter()
+            .position(|piece| piece.pos == *pos)
+            .ok_or(EngineError::NoPieceAt {
+                player_id,
+                pos: *pos,
+            })
+    }
+
+    // Works out the battle that an action of the current player would cause. This uses the actual
+    // ranks of both pieces, so it is only meaningful when the enemy ranks are known.
+    pub fn resolve_battle(&self, action: &Action) -> Result<Option<Battle>, EngineError> {
+        let id = self.current_player_id;
+        let other_id = (id + 1) % 2;
+
+        let Some(&Piece {
+            rank: enemy_rank, ..
+        }) = self.piece_at(other_id, &action.to)
+        else {
+            return Ok(None);
+        };
+        let friend_rank = self.pieces[id][self.piece_index(id, &action.from)?].rank;
+
+        // A captured flag doesn't fight back.
+        let (enemy_died, friend_died) = if enemy_rank == Rank::Flag {
+            (true, false)
+        } else {
+            battle_casualties(&self.ruleset.battle, &enemy_rank, &friend_rank)?
+        };
+
+        // The swap is a dirty trick to make sure that the rank of player 0 is at index 0.
+        // If the current player is 0 then the swap is a noop. If the current player is
+        // 1 (meaning the order is incorrect), then the elements are swapped.
+        let mut ranks = [friend_rank, enemy_rank];
+        ranks.as_mut_slice().swap(0, id);
+
+        let mut has_died = [friend_died, enemy_died];
+        has_died.as_mut_slice().swap(0, id);
+
+        Ok(Some(Battle { ranks, has_died }))
     }
 
-    pub fn update_with_turn(&mut self, turn: &Turn) {
+    // Plays an action of the current player, see `resolve_battle` for how battles are decided.
+    pub fn apply_action(&mut self, action: &Action) -> Result<Undo, EngineError> {
+        let turn = Turn {
+            player_id: self.current_player_id,
+            action: *action,
+            battle: self.resolve_battle(action)?,
+            piece_ids: [None; 2],
+        };
+
+        self.update_with_turn(&turn)
+    }
+
+    // The state is left untouched when the turn can't be applied.
+    pub fn update_with_turn(&mut self, turn: &Turn) -> Result<Undo, EngineError> {
         let id = turn.player_id;
+        let other_id = (id + 1) % 2;
 
-        self.bitmaps[id].set(turn.action.from.to_bit_index(), false);
-        self.bitmaps[id].set(turn.action.to.to_bit_index(), true);
+        let board = self.ruleset.board;
 
-        let mut piece = self.pieces[id]
-            .iter_mut()
-            .find(|Piece { pos, .. }| *pos == turn.action.from)
-            .unwrap();
+        if !turn.action.to.is_valid_map_position(&board) {
+            return Err(EngineError::InvalidSquare {
+                pos: turn.action.to,
+            });
+        }
 
+        let idx = self.piece_index(id, &turn.action.from)?;
+        let other_idx = match turn.battle {
+            Some(_) => Some(self.piece_index(other_id, &turn.action.to)?),
+            None => None,
+        };
+
+        let mut undo = Undo {
+            turn: *turn,
+            changed: [None; 2],
+            removed: [false; 2],
+            current_player_id: self.current_player_id,
+            turn_count: self.turn_count,
+            bitmaps: self.bitmaps,
+            streaks: self.streaks,
+            chases: self.chases,
+            hash: self.hash,
+        };
+
+        self.bitmaps[id].set(turn.action.from.to_bit_index(&board), false);
+        self.bitmaps[id].set(turn.action.to.to_bit_index(&board), true);
+
+        undo.turn.piece_ids[id] = Some(self.pieces[id][idx].id);
+        undo.changed[id] = Some((idx, self.pieces[id][idx]));
+        self.hash ^= piece_hash(id, &self.pieces[id][idx], &board);
+
+        let piece = &mut self.pieces[id][idx];
         piece.pos = turn.action.to;
         piece.has_moved = true;
 
-        if let Some(Battle { has_died, .. }) = turn.battle {
-            for id in [0, 1] {
-                let idx = self.pieces[id]
-                    .iter()
-                    .position(|Piece { pos, .. }| *pos == turn.action.to)
-                    .unwrap();
+        // Only scouts can move more than one square, so the move gives the rank away.
+        if turn.action.distance() > 1 {
+            piece.rank = Rank::Scout;
+            piece.is_revealed = true;
+        }
+
+        if self.ruleset.movement.two_square_rule {
+            self.streaks[id] = Some(MoveStreak::after(self.streaks[id], &turn.action));
+        }
+
+        if let (Some(Battle { ranks, has_died }), Some(other_idx)) = (turn.battle, other_idx) {
+            undo.turn.piece_ids[other_id] = Some(self.pieces[other_id][other_idx].id);
+            undo.changed[other_id] = Some((other_idx, self.pieces[other_id][other_idx]));
+            self.hash ^= piece_hash(other_id, &self.pieces[other_id][other_idx], &board);
+
+            for (id, idx) in [(id, idx), (other_id, other_idx)] {
+                undo.removed[id] = has_died[id];
 
+                // The ranks of the opponent are only learnt from the battle when the turns come
+                // from the server.
+                self.pieces[id][idx].rank = ranks[id];
                 self.pieces[id][idx].is_revealed = true;
 
-                self.bitmaps[id].set(turn.action.to.to_bit_index(), !has_died[id]);
+                self.bitmaps[id].set(turn.action.to.to_bit_index(&board), !has_died[id]);
 
                 if has_died[id] {
-                    self.pieces[id].swap_remove(idx);
+                    let piece = self.pieces[id].swap_remove(idx);
+                    self.graveyards[id].push(CapturedPiece {
+                        id: piece.id,
+                        rank: ranks[id],
+                        turn: self.turn_count,
+                        captured_by: ranks[(id + 1) % 2],
+                    });
                 }
             }
         }
 
+        if self.ruleset.movement.more_squares_rule {
+            self.update_chases(turn);
+        }
+
+        // The hashes of the changed pieces were removed above, so the hashes of the survivors have
+        // to be added back.
+        for id in [0, 1] {
+            if let Some((idx, _)) = undo.changed[id].filter(|_| !undo.removed[id]) {
+                self.hash ^= piece_hash(id, &self.pieces[id][idx], &board);
+            }
+        }
+
+        let next_player_id = (turn.player_id + 1) % 2;
+        self.hash ^= side_hash(self.current_player_id) ^ side_hash(next_player_id);
+
         self.turn_count += 1;
-        self.current_player_id = (turn.player_id + 1) % 2
+        self.current_player_id = next_player_id;
+
+        Ok(undo)
+    }
+
+    // Takes back the turn that produced `undo`. Turns have to be taken back in the reverse order
+    // they were played in.
+    pub fn unmake(&mut self, undo: &Undo) {
+        for id in [0, 1] {
+            let Some((idx, piece)) = undo.changed[id] else {
+                continue;
+            };
+
+            if undo.removed[id] {
+                self.graveyards[id].pop();
+
+                // Reverses the `swap_remove` that took the piece off the board.
+                let last = self.pieces[id].len();
+                self.pieces[id].push(piece);
+                self.pieces[id].swap(idx, last);
+            } else {
+                self.pieces[id][idx] = piece;
+            }
+        }
+
+        self.current_player_id = undo.current_player_id;
+        self.turn_count = undo.turn_count;
+        self.bitmaps = undo.bitmaps;
+        self.streaks = undo.streaks;
+        self.chases = undo.chases;
+        self.hash = undo.hash;
+    }
+
+    fn update_chases(&mut self, turn: &Turn) {
+        let id = turn.player_id;
+        let other_id = (id + 1) % 2;
+        let board = &self.ruleset.board;
+
+        // A chase against the current player only goes on if the chased piece fled.
+        self.chases[other_id] = self.chases[other_id]
+            .filter(|chase| turn.battle.is_none() && chase.chased == turn.action.from)
+            .map(|chase| Chase {
+                chased: turn.action.to,
+                ..chase
+            });
+
+        if turn.battle.is_some() {
+            self.chases[id] = None;
+            return;
+        }
+
+        self.chases[id] = match self.chases[id] {
+            Some(chase) if chase.is_continued_by(&turn.action) => {
+                Some(chase.continued(&turn.action, board))
+            }
+            _ => {
+                // Start chasing an enemy piece next to the destination. The piece the enemy moved
+                // last is the most likely target, so that one is preferred.
+                let threatened = turn.action.to.neighbours().into_iter().filter(|pos| {
+                    pos.is_valid_map_position(board)
+                        && self.bitmaps[other_id].get(pos.to_bit_index(board))
+                });
+
+                let last_moved = self.streaks[other_id].map(|streak| streak.to);
+
+                threatened
+                    .clone()
+                    .find(|pos| Some(*pos) == last_moved)
+                    .or_else(|| threatened.clone().next())
+                    .map(|chased| Chase::new(&turn.action, chased, board))
+            }
+        };
     }
 }
 
@@ -220,7 +641,9 @@ pub fn validate_action(state: &State, action: &Action) -> Result<(), ActionError
         .iter()
         .find(|p| p.pos == action.from);
 
-    let Some(friend) = friend else { return Err(NoFriendlyPieceOnFromPosition); };
+    let Some(friend) = friend else {
+        return Err(NoFriendlyPieceOnFromPosition);
+    };
 
     if !friend.rank.is_moveable() {
         return Err(FriendlyPieceIsNotMoveable);
@@ -233,7 +656,7 @@ pub fn validate_action(state: &State, action: &Action) -> Result<(), ActionError
         return Err(ToPositionOccpuiedByFriend);
     }
 
-    if !action.to.is_valid_map_position() {
+    if !action.to.is_valid_map_position(&state.ruleset.board) {
         return Err(ToPositionIsAnInvalidMapPosition);
     }
 
@@ -241,20 +664,284 @@ pub fn validate_action(state: &State, action: &Action) -> Result<(), ActionError
         return Err(MovementIsNotStraight);
     }
 
-    let is_valid_movement_distance = if friend.rank == Rank::Scout {
-        action.distance()
-            <= scout_max_steps_with_stepper(
-                action.direction().to_stepper(),
-                &action.from,
-                &state.bitmaps[state.current_player_id],
-                &state.bitmaps[(state.current_player_id + 1) % 2],
-            )
-    } else {
-        action.distance() == 1
-    };
+    let is_valid_movement_distance =
+        if friend.rank == Rank::Scout && state.ruleset.movement.scouts_move_far {
+            action.distance()
+                <= scout_max_steps_with_stepper(
+                    action.direction().to_stepper(),
+                    &action.from,
+                    &state.ruleset.board,
+                    &state.bitmaps[state.current_player_id],
+                    &state.bitmaps[(state.current_player_id + 1) % 2],
+                )
+        } else {
+            action.distance() == 1
+        };
     if !is_valid_movement_distance {
         return Err(InvalidMovementDistance);
     }
 
+    if state.streaks[state.current_player_id].is_some_and(|streak| streak.forbids(action)) {
+        return Err(ViolatesTwoSquareRule);
+    }
+
+    if state.chases[state.current_player_id]
+        .is_some_and(|chase| chase.forbids(action, &state.ruleset.board))
+    {
+        return Err(ViolatesMoreSquaresRule);
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::logic::all_possible_moves;
+    use crate::testing::random_start;
+    use rand::{seq::SliceRandom, SeedableRng};
+    use rand_xoshiro::Xoshiro256StarStar;
+
+    #[test]
+    fn test_unmake_restores_the_exact_state_and_hash() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(1);
+        let mut actions = Vec::new();
+
+        for ruleset in Ruleset::presets() {
+            let mut state = random_start(&mut rng, ruleset);
+            let mut history = vec![(state, None)];
+
+            for _ in 0..200 {
+                actions.clear();
+                all_possible_moves(&state, &mut actions);
+
+                let Some(action) = actions.choose(&mut rng) else {
+                    break;
+                };
+
+                let before = state;
+                let undo = state.apply_action(action).unwrap();
+                history.push((state, Some(undo)));
+
+                // The pieces keep their ids when they move, and the ids of the pieces that die
+                // in a battle go to the graveyard.
+                for id in [0, 1] {
+                    let Some(piece_id) = undo.turn.piece_ids[id] else {
+                        assert!(id != undo.turn.player_id && undo.turn.battle.is_none());
+                        continue;
+                    };
+
+                    match state.pieces[id].iter().find(|p| p.id == piece_id) {
+                        Some(piece) => assert_eq!(piece.pos, action.to),
+                        None => assert_eq!(state.graveyards[id].last().unwrap().id, piece_id),
+                    }
+                }
+
+                // The ids are the same when the turn is played on the reversed state.
+                let board = &ruleset.board;
+                let reversed_turn = Turn {
+                    piece_ids: [None; 2],
+                    ..undo.turn.reversed(board)
+                };
+                let reversed_undo = before.reversed().update_with_turn(&reversed_turn).unwrap();
+                assert_eq!(
+                    reversed_undo.turn.piece_ids,
+                    undo.turn.reversed(board).piece_ids
+                );
+
+                // Every piece is either on the board or in the graveyard.
+                for id in [0, 1] {
+                    let piece_count = state.pieces[id].len() + state.graveyards[id].len();
+                    assert_eq!(piece_count, ruleset.armies[id].len());
+                }
+
+                assert_eq!(state.hash, state.compute_hash());
+                assert_eq!(state.reversed().reversed().hash, state.hash);
+                assert_ne!(state.reversed().hash, state.hash);
+
+                if undo
+                    .turn
+                    .battle
+                    .is_some_and(|battle| battle.ranks.contains(&Rank::Flag))
+                {
+                    break;
+                }
+            }
+
+            while let Some((after, undo)) = history.pop() {
+                assert!(state == after);
+
+                if let Some(undo) = undo {
+                    state.unmake(&undo);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_position_key_includes_the_move_streaks() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(5);
+        let state = random_start(&mut rng, Ruleset::barrage());
+
+        let mut actions = Vec::new();
+        all_possible_moves(&state, &mut actions);
+
+        let mut streaking = state;
+        streaking.streaks[0] = Some(MoveStreak::new(&actions[0]));
+
+        assert_eq!(state.hash, streaking.hash);
+        assert_ne!(state.position_key(), streaking.position_key());
+    }
+
+    #[test]
+    fn test_moves_reveal_what_a_piece_can_be() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(2);
+        let mut actions = Vec::new();
+        let mut state = random_start(&mut rng, Ruleset::barrage());
+
+        // Plays random moves until a scout jumps more than one square.
+        let action = loop {
+            actions.clear();
+            all_possible_moves(&state, &mut actions);
+
+            let action = *actions.choose(&mut rng).unwrap();
+            let is_battle = state
+                .piece_at(1 - state.current_player_id, &action.to)
+                .is_some();
+
+            if action.distance() > 1 && !is_battle {
+                break action;
+            }
+
+            state.apply_action(&action).unwrap();
+        };
+
+        let id = state.current_player_id;
+        let piece = *state
+            .obscured_for(1 - id)
+            .piece_at(id, &action.from)
+            .unwrap();
+        assert_eq!(piece.rank, Rank::Unknown);
+
+        state.apply_action(&action).unwrap();
+
+        let piece = *state.obscured_for(1 - id).piece_at(id, &action.to).unwrap();
+        assert_eq!(piece.rank, Rank::Scout);
+        assert!(piece.could_be(Rank::Scout));
+        assert!(!piece.could_be(Rank::Bomb));
+        assert!(!piece.could_be(Rank::Miner));
+    }
+
+    // A 10x10 board with a flag and a scout for both players, as the server would send it.
+    fn json_state(
+        extra_tiles: &[serde_json::Value],
+        last_move: serde_json::Value,
+    ) -> GameStateJson {
+        let mut board = Vec::new();
+        for (x, y) in itertools::iproduct!(0..10, 0..10) {
+            let owner = match (x, y) {
+                (0 | 1, 0) => Some(0),
+                (0 | 1, 9) => Some(1),
+                _ => None,
+            };
+            let rank = match (x, owner) {
+                (0, Some(0)) => Some("Flag"),
+                (1, Some(0)) => Some("Scout"),
+                _ => None,
+            };
+
+            board.push(serde_json::json!({
+                "Rank": rank,
+                "Owner": owner,
+                "IsWater": false,
+                "Coordinate": { "X": x, "Y": y },
+            }));
+        }
+        board.extend_from_slice(extra_tiles);
+
+        serde_json::from_value(serde_json::json!({
+            "ActivePlayer": 1,
+            "TurnNumber": 1,
+            "Board": board,
+            "LastMove": last_move,
+            "BattleResult": null,
+        }))
+        .unwrap()
+    }
+
+    fn json_move(from: (u8, u8), to: (u8, u8)) -> serde_json::Value {
+        serde_json::json!({
+            "From": { "X": from.0, "Y": from.1 },
+            "To": { "X": to.0, "Y": to.1 },
+        })
+    }
+
+    #[test]
+    fn test_malformed_server_input_gives_errors() {
+        let ruleset = Ruleset::classic();
+
+        let missing_move = json_state(&[], serde_json::Value::Null);
+        assert_eq!(
+            Turn::try_from(missing_move).unwrap_err(),
+            EngineError::MissingLastMove
+        );
+
+        let mut third_player = json_state(&[], json_move((1, 0), (1, 1)));
+        third_player.active_player = 2;
+        assert_eq!(
+            Turn::try_from(third_player).unwrap_err(),
+            EngineError::InvalidPlayer { player_id: 2 }
+        );
+
+        let stranger = serde_json::json!({
+            "Rank": null,
+            "Owner": 2,
+            "IsWater": false,
+            "Coordinate": { "X": 5, "Y": 5 },
+        });
+        let with_stranger = json_state(&[stranger], serde_json::Value::Null);
+        assert_eq!(
+            State::new_from_json_state(&with_stranger, ruleset).err(),
+            Some(EngineError::InvalidPlayer { player_id: 2 })
+        );
+
+        let crowd = itertools::iproduct!(0..10, 1..6)
+            .map(|(x, y)| {
+                serde_json::json!({
+                    "Rank": "Scout",
+                    "Owner": 0,
+                    "IsWater": false,
+                    "Coordinate": { "X": x, "Y": y },
+                })
+            })
+            .collect::<Vec<_>>();
+        let crowded = json_state(&crowd, serde_json::Value::Null);
+        assert_eq!(
+            State::new_from_json_state(&crowded, ruleset).err(),
+            Some(EngineError::TooManyPieces { player_id: 0 })
+        );
+
+        let initial = json_state(&[], serde_json::Value::Null);
+        let mut state = State::new_from_json_state(&initial, ruleset).unwrap();
+        let hash = state.hash;
+
+        let turn = |from, to| Turn::try_from(json_state(&[], json_move(from, to))).unwrap();
+
+        assert_eq!(
+            state.update_with_turn(&turn((1, 0), (1, 12))).unwrap_err(),
+            EngineError::InvalidSquare {
+                pos: Position { x: 1, y: 12 }
+            }
+        );
+        assert_eq!(
+            state.update_with_turn(&turn((5, 5), (5, 6))).unwrap_err(),
+            EngineError::NoPieceAt {
+                player_id: 0,
+                pos: Position { x: 5, y: 5 }
+            }
+        );
+        assert_eq!(state.hash, hash);
+
+        state.update_with_turn(&turn((1, 0), (1, 1))).unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+use crate::boardbitmap::MAX_SQUARES;
+use crate::game::{Board, Piece, RANK_COUNT};
+
+// `Rank::Unknown` gets its own keys, so there is one more rank than `RANK_COUNT`.
+const RANK_KEY_COUNT: usize = RANK_COUNT + 1;
+
+static RANK_KEYS: [u64; 2 * MAX_SQUARES * RANK_KEY_COUNT] = generate_keys(0x5eed_0001);
+static REVEALED_KEYS: [u64; 2 * MAX_SQUARES] = generate_keys(0x5eed_0002);
+static MOVED_KEYS: [u64; 2 * MAX_SQUARES] = generate_keys(0x5eed_0003);
+const SIDE_KEY: u64 = generate_keys::<1>(0x5eed_0004)[0];
+
+// The keys are generated with SplitMix64 from fixed seeds, so hashes are the same across runs and
+// machines.
+const fn generate_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut state = seed;
+
+    let mut i = 0;
+    while i < N {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+
+    keys
+}
+
+pub fn piece_hash(player_id: usize, piece: &Piece, board: &Board) -> u64 {
+    let square = player_id * MAX_SQUARES + piece.pos.to_bit_index(board);
+
+    let mut hash = RANK_KEYS[square * RANK_KEY_COUNT + piece.rank as usize];
+
+    if piece.is_revealed {
+        hash ^= REVEALED_KEYS[square];
+    }
+
+    if piece.has_moved {
+        hash ^= MOVED_KEYS[square];
+    }
+
+    hash
+}
+
+pub fn side_hash(current_player_id: usize) -> u64 {
+    if current_player_id == 1 {
+        SIDE_KEY
+    } else {
+        0
+    }
+}
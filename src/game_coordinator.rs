@@ -1,126 +1,704 @@
 use crate::{
-    bot::{Bot, BotOrienter},
-    game::logic::{battle_casualties, has_a_possible_move},
-    game::{validate_action, Battle, Piece, Rank, State, Turn},
+    bot::{Bot, BotOrienter, GameResult},
+    game::logic::has_a_possible_move,
+    game::{
+        validate_action, validate_placement, Action, ActionError, Battle, Beliefs, Piece, Position,
+        Rank, Ruleset, State, Turn, MATERIAL_VALUES, RANK_COUNT,
+    },
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
 
 pub struct GameCoordinator {
     bots: [Box<dyn Bot>; 2],
     max_turn_count: usize,
+    repetition_limit: Option<usize>,
+    adjudication: Option<Adjudication>,
+    illegal_action_policy: IllegalActionPolicy,
     state: State,
+    seen_positions: HashMap<u64, usize>,
+    stats: GameStats,
+}
+
+// What happens when a bot makes an illegal action.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IllegalActionPolicy {
+    // The bot loses the game.
+    Forfeit,
+    // The bot is asked for another action, and loses once it runs out of retries.
+    Retry { max_retries: usize },
+    Panic,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WinReason {
+    FlagCaptured,
+    OpponentImmobile,
+    IllegalAction { action: Action, error: ActionError },
+    Adjudication,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DrawReason {
+    BothImmobile,
+    Repetition,
+    Agreement,
+    TurnLimit,
 }
 
 #[derive(Debug)]
 pub enum Outcome {
-    ReachedMaxTurnCount(usize),
-    Win { winner: usize, turn_count: usize },
+    Win {
+        winner: usize,
+        reason: WinReason,
+        turn_count: usize,
+    },
+    Draw {
+        reason: DrawReason,
+        turn_count: usize,
+    },
+}
+
+impl Outcome {
+    pub fn result_for(&self, player_id: usize) -> GameResult {
+        match self {
+            Outcome::Win { winner, .. } if *winner == player_id => GameResult::Win,
+            Outcome::Win { .. } => GameResult::Loss,
+            Outcome::Draw { .. } => GameResult::Draw,
+        }
+    }
+}
+
+// How a game ended, together with the statistics gathered while playing it.
+#[derive(Debug)]
+pub struct GameRecord {
+    pub outcome: Outcome,
+    pub stats: GameStats,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IllegalAction {
+    pub player_id: usize,
+    pub action: Action,
+    pub error: ActionError,
+    pub turn: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Reveal {
+    pub player_id: usize,
+    pub piece_id: u8,
+    pub rank: Rank,
+    pub pos: Position,
+    pub turn: usize,
+}
+
+// Statistics gathered while playing a game. Ranks are used as indices.
+#[derive(Debug, Default, Clone)]
+pub struct GameStats {
+    pub battles: usize,
+    pub pieces_lost: [[usize; RANK_COUNT]; 2],
+    pub moves: [[usize; RANK_COUNT]; 2],
+    // The first time each piece was revealed, in the order it happened.
+    pub reveals: Vec<Reveal>,
+    // Every illegal action, including the ones that were retried.
+    pub illegal_actions: Vec<IllegalAction>,
+}
+
+// Decides the winner of a game that reached the turn limit by comparing the material that both
+// players have left. The game is still a draw when the difference is smaller than `margin`.
+#[derive(Debug, Clone, Copy)]
+pub struct Adjudication {
+    pub values: [u32; RANK_COUNT],
+    pub margin: u32,
+}
+
+impl Adjudication {
+    pub fn material(margin: u32) -> Adjudication {
+        Adjudication {
+            values: MATERIAL_VALUES,
+            margin,
+        }
+    }
+
+    pub fn winner(&self, state: &State) -> Option<usize> {
+        let [material_0, material_1] = [0, 1].map(|id| {
+            state.pieces[id]
+                .iter()
+                .map(|Piece { rank, .. }| self.values[*rank as usize])
+                .sum::<u32>()
+        });
+
+        if material_0 >= material_1 + self.margin {
+            Some(0)
+        } else if material_1 >= material_0 + self.margin {
+            Some(1)
+        } else {
+            None
+        }
+    }
 }
 
 impl GameCoordinator {
-    pub fn new(p0: Box<dyn Bot>, p1: Box<dyn Bot>, max_turn_count: usize) -> GameCoordinator {
+    pub fn new(
+        p0: Box<dyn Bot>,
+        p1: Box<dyn Bot>,
+        ruleset: Ruleset,
+        max_turn_count: usize,
+    ) -> Result<GameCoordinator> {
         let mut p0 = Box::new(BotOrienter::new(p0, 0));
         let mut p1 = Box::new(BotOrienter::new(p1, 1));
 
-        let placements = [p0.get_initial_placements(), p1.get_initial_placements()];
+        let placements = [
+            p0.get_initial_placements(&ruleset),
+            p1.get_initial_placements(&ruleset),
+        ];
+
+        for (player_id, placement) in placements.iter().enumerate() {
+            validate_placement(
+                placement,
+                &ruleset.armies[player_id],
+                &ruleset.board,
+                player_id,
+            )
+            .with_context(|| format!("Player {player_id} made an invalid placement"))?;
+        }
+
+        let state = State::new_with_placements(ruleset, &[&placements[0], &placements[1]]);
+
+        Ok(GameCoordinator::with_oriented_bots(
+            [p0, p1],
+            state,
+            max_turn_count,
+        ))
+    }
+
+    // Continues a game from the given state, for example one sampled with a `Determinizer`. All
+    // ranks have to be known, because the coordinator decides the battles.
+    pub fn from_state(
+        p0: Box<dyn Bot>,
+        p1: Box<dyn Bot>,
+        state: State,
+        max_turn_count: usize,
+    ) -> Result<GameCoordinator> {
+        for player_id in [0, 1] {
+            if state.pieces[player_id]
+                .iter()
+                .any(|piece| piece.rank == Rank::Unknown)
+            {
+                bail!("Player {player_id} has pieces with unknown ranks");
+            }
+        }
+
+        let bots: [Box<dyn Bot>; 2] = [
+            Box::new(BotOrienter::new(p0, 0)),
+            Box::new(BotOrienter::new(p1, 1)),
+        ];
+
+        Ok(GameCoordinator::with_oriented_bots(
+            bots,
+            state,
+            max_turn_count,
+        ))
+    }
 
+    fn with_oriented_bots(
+        bots: [Box<dyn Bot>; 2],
+        state: State,
+        max_turn_count: usize,
+    ) -> GameCoordinator {
         GameCoordinator {
-            bots: [p0, p1],
+            bots,
             max_turn_count,
-            state: State::new_with_placements(&[&placements[0], &placements[1]]),
+            repetition_limit: Some(3),
+            adjudication: None,
+            illegal_action_policy: IllegalActionPolicy::Forfeit,
+            state,
+            seen_positions: HashMap::new(),
+            stats: GameStats::default(),
         }
     }
 
-    pub fn play(&mut self) -> Result<Outcome> {
+    pub fn with_illegal_action_policy(mut self, policy: IllegalActionPolicy) -> GameCoordinator {
+        self.illegal_action_policy = policy;
+        self
+    }
+
+    // The game is a draw once the same position occurs this many times, three by default.
+    pub fn with_repetition_limit(mut self, repetition_limit: Option<usize>) -> GameCoordinator {
+        self.repetition_limit = repetition_limit;
+        self
+    }
+
+    pub fn with_adjudication(mut self, adjudication: Adjudication) -> GameCoordinator {
+        self.adjudication = Some(adjudication);
+        self
+    }
+
+    // Plays the game to the end, and then tells both bots how it went.
+    pub fn play(&mut self) -> Result<GameRecord> {
+        let outcome = self.play_until_end()?;
+
+        for (player_id, bot) in self.bots.iter_mut().enumerate() {
+            bot.on_game_end(outcome.result_for(player_id));
+        }
+
+        Ok(GameRecord {
+            outcome,
+            stats: std::mem::take(&mut self.stats),
+        })
+    }
+
+    fn play_until_end(&mut self) -> Result<Outcome> {
         while self.state.turn_count < self.max_turn_count {
             let current_player_id = self.state.current_player_id;
             let other_player_id = (current_player_id + 1) % 2;
 
-            // If the current player has no possible moves, then they immediately lose.
-            let player_can_move = has_a_possible_move(
-                self.state.pieces[current_player_id].as_slice(),
-                &self.state.bitmaps[current_player_id],
-                &self.state.bitmaps[other_player_id],
-            );
+            let occurrences = self
+                .seen_positions
+                .entry(self.state.position_key())
+                .or_insert(0);
+            *occurrences += 1;
 
-            if !player_can_move {
-                return Ok(Outcome::Win {
-                    winner: other_player_id,
+            if self
+                .repetition_limit
+                .is_some_and(|limit| *occurrences >= limit)
+            {
+                return Ok(Outcome::Draw {
+                    reason: DrawReason::Repetition,
                     turn_count: self.state.turn_count,
                 });
             }
 
-            let action = {
-                // Make a copy of the current state where all the opponents ranks are hidden, unless
-                // they have been previously revealed.
-                let mut obscured_state = self.state;
-                for Piece {
-                    rank, is_revealed, ..
-                } in obscured_state.pieces[other_player_id].iter_mut()
-                {
-                    if !*is_revealed {
-                        *rank = Rank::Unknown;
-                    }
+            // If the current player has no possible moves, then they immediately lose, unless
+            // the rules let them pass instead. Nobody wins when neither player can move.
+            if !has_a_possible_move(&self.state) {
+                let mut passed_state = self.state;
+                passed_state.pass_turn();
+
+                if !has_a_possible_move(&passed_state) {
+                    return Ok(Outcome::Draw {
+                        reason: DrawReason::BothImmobile,
+                        turn_count: self.state.turn_count,
+                    });
                 }
 
-                self.bots[current_player_id].get_action(obscured_state)
-            };
+                if self.state.ruleset.end.immobile_player_loses {
+                    return Ok(Outcome::Win {
+                        winner: other_player_id,
+                        reason: WinReason::OpponentImmobile,
+                        turn_count: self.state.turn_count,
+                    });
+                }
 
-            if cfg!(debug_assertions) {
-                validate_action(&self.state, &action)?;
+                self.state.pass_turn();
+                continue;
             }
 
-            let has_enemy_at_destination =
-                self.state.bitmaps[other_player_id].get(action.to.to_bit_index());
-
-            let battle = if has_enemy_at_destination {
-                // Find the piece that is on the destination square.
-                let Piece {
-                    rank: enemy_rank, ..
-                } = self.state.pieces[other_player_id]
-                    .iter()
-                    .find(|Piece { pos, .. }| *pos == action.to)
-                    .unwrap();
+            if self.agree_to_draw() {
+                return Ok(Outcome::Draw {
+                    reason: DrawReason::Agreement,
+                    turn_count: self.state.turn_count,
+                });
+            }
 
-                // If the enemy piece is the flag, the game is over.
-                if *enemy_rank == Rank::Flag {
+            let action = match self.request_action() {
+                Ok(action) => action,
+                Err(IllegalAction { action, error, .. }) => {
                     return Ok(Outcome::Win {
-                        winner: current_player_id,
-                        turn_count: self.state.turn_count + 1,
+                        winner: other_player_id,
+                        reason: WinReason::IllegalAction { action, error },
+                        turn_count: self.state.turn_count,
                     });
                 }
+            };
 
-                // Find the piece we are trying to move to the destination square.
-                let Piece {
-                    rank: friend_rank, ..
-                } = self.state.pieces[current_player_id]
-                    .iter()
-                    .find(|Piece { pos, .. }| *pos == action.from)
-                    .unwrap();
+            let turn = Turn {
+                player_id: current_player_id,
+                action,
+                battle: self.state.resolve_battle(&action)?,
+                piece_ids: [None; 2],
+            };
 
-                let (enemy_died, friend_died) = battle_casualties(enemy_rank, friend_rank);
+            self.record_turn(&turn);
 
-                // The swap is a dirty trick to make sure that the rank of player 0 is at index 0.
-                // If the current player is 0 then the swap is a noop. If the current player is
-                // 1 (meaning the order is incorrect), then the elements are swapped.
-                let mut ranks = [*friend_rank, *enemy_rank];
-                ranks.as_mut_slice().swap(0, current_player_id);
+            // If the enemy piece is the flag, the game is over.
+            if turn
+                .battle
+                .is_some_and(|Battle { ranks, .. }| ranks[other_player_id] == Rank::Flag)
+            {
+                return Ok(Outcome::Win {
+                    winner: current_player_id,
+                    reason: WinReason::FlagCaptured,
+                    turn_count: self.state.turn_count + 1,
+                });
+            }
+
+            self.state.update_with_turn(&turn)?;
+        }
 
-                let mut has_died = [friend_died, enemy_died];
-                has_died.as_mut_slice().swap(0, current_player_id);
+        let winner = self
+            .adjudication
+            .and_then(|adjudication| adjudication.winner(&self.state));
 
-                Some(Battle { ranks, has_died })
+        Ok(match winner {
+            Some(winner) => Outcome::Win {
+                winner,
+                reason: WinReason::Adjudication,
+                turn_count: self.state.turn_count,
+            },
+            None => Outcome::Draw {
+                reason: DrawReason::TurnLimit,
+                turn_count: self.state.turn_count,
+            },
+        })
+    }
+
+    // Asks the current player for an action until it makes a legal one, or until the illegal
+    // action policy gives up on it.
+    fn request_action(&mut self) -> Result<Action, IllegalAction> {
+        let player_id = self.state.current_player_id;
+
+        let max_retries = match self.illegal_action_policy {
+            IllegalActionPolicy::Retry { max_retries } => max_retries,
+            _ => 0,
+        };
+
+        for _ in 0..max_retries {
+            match self.request_action_once() {
+                Ok(action) => return Ok(action),
+                Err(illegal_action) => self.stats.illegal_actions.push(illegal_action),
+            }
+        }
+
+        let illegal_action = match self.request_action_once() {
+            Ok(action) => return Ok(action),
+            Err(illegal_action) => illegal_action,
+        };
+
+        self.stats.illegal_actions.push(illegal_action);
+
+        if self.illegal_action_policy == IllegalActionPolicy::Panic {
+            panic!(
+                "Player {player_id} made an illegal action {:?}: {}",
+                illegal_action.action, illegal_action.error
+            );
+        }
+
+        Err(illegal_action)
+    }
+
+    fn request_action_once(&mut self) -> Result<Action, IllegalAction> {
+        let player_id = self.state.current_player_id;
+
+        // The bot gets a copy of the current state where all the opponents ranks are hidden,
+        // unless they have been previously revealed.
+        let state = self.state.obscured_for(player_id);
+        let beliefs = Beliefs::new(&state, (player_id + 1) % 2);
+        let action = self.bots[player_id].get_action(state, &beliefs);
+
+        validate_action(&self.state, &action).map_err(|error| IllegalAction {
+            player_id,
+            action,
+            error,
+            turn: self.state.turn_count,
+        })?;
+
+        Ok(action)
+    }
+
+    // Has to be called before the turn is applied to the state.
+    fn record_turn(&mut self, turn: &Turn) {
+        let Turn {
+            player_id, action, ..
+        } = turn;
+
+        let mut revealed_by_move = false;
+
+        if let Some(&Piece {
+            id: piece_id,
+            rank,
+            is_revealed,
+            ..
+        }) = self.state.piece_at(*player_id, &action.from)
+        {
+            self.stats.moves[*player_id][rank as usize] += 1;
+
+            // Scouts reveal themselves by moving more than one square.
+            if !is_revealed && action.distance() > 1 {
+                revealed_by_move = true;
+                self.stats.reveals.push(Reveal {
+                    player_id: *player_id,
+                    piece_id,
+                    rank,
+                    pos: action.from,
+                    turn: self.state.turn_count,
+                });
+            }
+        }
+
+        let Some(Battle { ranks, has_died }) = turn.battle else {
+            return;
+        };
+
+        self.stats.battles += 1;
+
+        for id in [0, 1] {
+            if has_died[id] {
+                self.stats.pieces_lost[id][ranks[id] as usize] += 1;
+            }
+
+            let pos = if id == *player_id {
+                action.from
             } else {
-                None
+                action.to
             };
 
-            self.state.update_with_turn(&Turn {
-                player_id: current_player_id,
-                action,
-                battle,
-            });
+            if id == *player_id && revealed_by_move {
+                continue;
+            }
+
+            if let Some(&Piece {
+                id: piece_id,
+                is_revealed: false,
+                ..
+            }) = self.state.piece_at(id, &pos)
+            {
+                self.stats.reveals.push(Reveal {
+                    player_id: id,
+                    piece_id,
+                    rank: ranks[id],
+                    pos,
+                    turn: self.state.turn_count,
+                });
+            }
         }
+    }
+
+    // The current player may offer a draw before they move, which the opponent can accept.
+    fn agree_to_draw(&mut self) -> bool {
+        let current_player_id = self.state.current_player_id;
+        let other_player_id = (current_player_id + 1) % 2;
+
+        self.bots[current_player_id].offers_draw(self.state.obscured_for(current_player_id))
+            && self.bots[other_player_id].accepts_draw(self.state.obscured_for(other_player_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::logic::all_possible_moves;
+    use crate::game::{Army, Position};
+
+    // Moves a piece back and forth between the same two squares.
+    struct ShuffleBot {
+        last: Option<Action>,
+        agrees_to_draw: bool,
+    }
+
+    impl ShuffleBot {
+        fn new(agrees_to_draw: bool) -> Box<ShuffleBot> {
+            Box::new(ShuffleBot {
+                last: None,
+                agrees_to_draw,
+            })
+        }
+    }
+
+    impl Bot for ShuffleBot {
+        fn get_initial_placements(&mut self, _ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+            unreachable!("The games start from a state")
+        }
+
+        fn get_action(&mut self, state: State, _beliefs: &Beliefs) -> Action {
+            let mut actions = Vec::new();
+            all_possible_moves(&state, &mut actions);
+
+            let back = self
+                .last
+                .map(|Action { from, to }| Action { from: to, to: from });
+            let action = back
+                .filter(|back| actions.contains(back))
+                .unwrap_or(actions[0]);
+
+            self.last = Some(action);
+            action
+        }
+
+        fn offers_draw(&mut self, _state: State) -> bool {
+            self.agrees_to_draw
+        }
+
+        fn accepts_draw(&mut self, _state: State) -> bool {
+            self.agrees_to_draw
+        }
+    }
+
+    // Always tries to move its flag.
+    struct FlagMoverBot;
+
+    impl Bot for FlagMoverBot {
+        fn get_initial_placements(&mut self, _ruleset: &Ruleset) -> Vec<(Rank, Position)> {
+            unreachable!("The games start from a state")
+        }
+
+        fn get_action(&mut self, state: State, _beliefs: &Beliefs) -> Action {
+            let flag = state.pieces[0]
+                .iter()
+                .find(|piece| piece.rank == Rank::Flag)
+                .unwrap();
+
+            Action {
+                from: flag.pos,
+                to: flag.pos.down(),
+            }
+        }
+    }
+
+    // A game on the standard board between two small armies that stay far apart.
+    fn game(
+        armies: [&[(Rank, Position)]; 2],
+        agrees_to_draw: bool,
+        max_turn_count: usize,
+    ) -> GameCoordinator {
+        let bots: [Box<dyn Bot>; 2] = [
+            ShuffleBot::new(agrees_to_draw),
+            ShuffleBot::new(agrees_to_draw),
+        ];
+        game_with_bots(bots, armies, max_turn_count)
+    }
+
+    fn game_with_bots(
+        [p0, p1]: [Box<dyn Bot>; 2],
+        armies: [&[(Rank, Position)]; 2],
+        max_turn_count: usize,
+    ) -> GameCoordinator {
+        let ranks = armies.map(|army| army.iter().map(|(rank, _)| *rank).collect::<Vec<_>>());
+        let mut ruleset = Ruleset {
+            armies: ranks.map(|ranks| Army::from_ranks(&ranks)),
+            ..Ruleset::classic()
+        };
+        ruleset.movement.two_square_rule = false;
+
+        let state = State::new_with_placements(ruleset, &armies);
+
+        GameCoordinator::from_state(p0, p1, state, max_turn_count).unwrap()
+    }
+
+    fn pos(x: u8, y: u8) -> Position {
+        Position { x, y }
+    }
+
+    #[test]
+    fn test_draw_and_adjudication_paths() {
+        let strong: &[(Rank, Position)] = &[
+            (Rank::Flag, pos(0, 0)),
+            (Rank::Marshal, pos(2, 0)),
+            (Rank::Sergeant, pos(4, 0)),
+        ];
+        let weak: &[(Rank, Position)] = &[(Rank::Flag, pos(9, 9)), (Rank::Sergeant, pos(7, 9))];
+        let immobile: &[(Rank, Position)] = &[(Rank::Flag, pos(0, 0)), (Rank::Bomb, pos(1, 0))];
+        let immobile_1: &[(Rank, Position)] = &[(Rank::Flag, pos(9, 9)), (Rank::Bomb, pos(8, 9))];
+
+        let draw_reason = |outcome: Outcome| match outcome {
+            Outcome::Draw { reason, .. } => Some(reason),
+            Outcome::Win { .. } => None,
+        };
+
+        // Without a limit, repeating positions doesn't end the game.
+        let outcome = game([strong, weak], false, 40)
+            .with_repetition_limit(None)
+            .play()
+            .unwrap()
+            .outcome;
+        assert_eq!(draw_reason(outcome), Some(DrawReason::TurnLimit));
+
+        // The pieces count as moved from turn 2 on, which is the first of the repeated positions.
+        let GameRecord { outcome, stats } = game([strong, weak], false, 40).play().unwrap();
+        assert!(matches!(
+            outcome,
+            Outcome::Draw {
+                reason: DrawReason::Repetition,
+                turn_count: 10,
+            }
+        ));
+        assert_eq!(stats.moves.iter().flatten().sum::<usize>(), 10);
+
+        let outcome = game([immobile, immobile_1], false, 40)
+            .play()
+            .unwrap()
+            .outcome;
+        assert_eq!(draw_reason(outcome), Some(DrawReason::BothImmobile));
+
+        let outcome = game([strong, weak], true, 40).play().unwrap().outcome;
+        assert_eq!(draw_reason(outcome), Some(DrawReason::Agreement));
+
+        // The marshal and sergeant are worth 12 against 2.
+        let outcome = game([strong, weak], false, 40)
+            .with_repetition_limit(None)
+            .with_adjudication(Adjudication::material(10))
+            .play()
+            .unwrap()
+            .outcome;
+        assert!(matches!(
+            outcome,
+            Outcome::Win {
+                winner: 0,
+                reason: WinReason::Adjudication,
+                ..
+            }
+        ));
+
+        let outcome = game([strong, weak], false, 40)
+            .with_repetition_limit(None)
+            .with_adjudication(Adjudication::material(11))
+            .play()
+            .unwrap()
+            .outcome;
+        assert_eq!(draw_reason(outcome), Some(DrawReason::TurnLimit));
+    }
+
+    fn illegal_action_game(policy: IllegalActionPolicy) -> (Outcome, usize) {
+        let armies: [&[(Rank, Position)]; 2] = [
+            &[(Rank::Flag, pos(0, 0)), (Rank::Sergeant, pos(4, 0))],
+            &[(Rank::Flag, pos(9, 9)), (Rank::Sergeant, pos(7, 9))],
+        ];
+
+        let GameRecord { outcome, stats } =
+            game_with_bots([Box::new(FlagMoverBot), ShuffleBot::new(false)], armies, 40)
+                .with_illegal_action_policy(policy)
+                .play()
+                .unwrap();
+        (outcome, stats.illegal_actions.len())
+    }
+
+    #[test]
+    fn test_illegal_actions_forfeit_after_the_retries() {
+        for (policy, attempts) in [
+            (IllegalActionPolicy::Forfeit, 1),
+            (IllegalActionPolicy::Retry { max_retries: 3 }, 4),
+        ] {
+            let (outcome, illegal_actions) = illegal_action_game(policy);
+
+            assert!(matches!(
+                outcome,
+                Outcome::Win {
+                    winner: 1,
+                    reason: WinReason::IllegalAction {
+                        error: ActionError::FriendlyPieceIsNotMoveable,
+                        ..
+                    },
+                    turn_count: 0,
+                }
+            ));
+            assert_eq!(illegal_actions, attempts);
+        }
+    }
 
-        Ok(Outcome::ReachedMaxTurnCount(self.max_turn_count))
+    #[test]
+    #[should_panic(expected = "Player 0 made an illegal action")]
+    fn test_illegal_actions_panic_with_the_panic_policy() {
+        illegal_action_game(IllegalActionPolicy::Panic);
     }
 }
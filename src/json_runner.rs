@@ -1,9 +1,10 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::io::Write;
 
-use crate::bot::{Bot, BotOrienter};
-use crate::game::{Action, Position, Rank, State};
+use crate::bot::{captures_flag, Bot, BotOrienter, GameResult};
+use crate::game::logic::has_a_possible_move;
+use crate::game::{validate_placement, Action, Beliefs, Position, Rank, Ruleset, State, Turn};
 
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -75,6 +76,21 @@ pub struct GameStateJson {
     pub battle_result: Option<BattleResultJson>,
 }
 
+// Sent instead of the next game state once the game is over, as `{"GameOver": {"Winner": 1}}` or
+// `{"GameOver": "Draw"}`.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct GameOverJson {
+    pub game_over: GameEndJson,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum GameEndJson {
+    Winner(usize),
+    Draw,
+}
+
 #[derive(Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PlacementJson {
@@ -109,33 +125,151 @@ fn write_json<T: Serialize>(obj: T) -> Result<()> {
     Ok(())
 }
 
-fn read_json<T: DeserializeOwned>() -> Result<T> {
+// The next line from the server, or None once it has closed the connection.
+fn read_line() -> Result<Option<String>> {
     let stdin = std::io::stdin();
     let mut line_buffer = String::new();
 
-    stdin.read_line(&mut line_buffer)?;
+    match stdin.read_line(&mut line_buffer)? {
+        0 => Ok(None),
+        _ => Ok(Some(line_buffer)),
+    }
+}
 
-    let json: T = serde_json::from_reader(line_buffer.as_bytes())?;
+fn read_json<T: DeserializeOwned>() -> Result<T> {
+    let line = read_line()?.context("The server closed the connection")?;
+
+    let json: T = serde_json::from_str(&line)?;
 
     Ok(json)
 }
 
-pub fn run_bot(bot: Box<dyn Bot>) -> Result<()> {
+enum ServerMessage {
+    GameState(GameStateJson),
+    GameOver(GameResult),
+}
+
+// Reads a line the server sent during the game, which is either the next game state or the
+// game-over message.
+fn parse_message(line: Option<&str>, player_id: usize) -> Result<ServerMessage> {
+    let line = line.context("The server closed the connection before the game was over")?;
+
+    if let Ok(GameOverJson { game_over }) = serde_json::from_str(line) {
+        return Ok(ServerMessage::GameOver(match game_over {
+            GameEndJson::Winner(winner) if winner == player_id => GameResult::Win,
+            GameEndJson::Winner(winner) if winner < 2 => GameResult::Loss,
+            GameEndJson::Winner(winner) => bail!("The server named player {winner} the winner"),
+            GameEndJson::Draw => GameResult::Draw,
+        }));
+    }
+
+    let game_state = serde_json::from_str(line)
+        .with_context(|| format!("The server sent an invalid game state: {}", line.trim()))?;
+
+    Ok(ServerMessage::GameState(game_state))
+}
+
+pub fn run_bot(bot: Box<dyn Bot>, ruleset: Ruleset) -> Result<()> {
     println!("bot-start");
 
     let PartialGameInitJson { you: player_id } = read_json()?;
 
     let mut bot = BotOrienter::new(bot, player_id);
 
-    write_json(SetupBoardCommandJson::from(bot.get_initial_placements()))?;
+    let placement = bot.get_initial_placements(&ruleset);
+
+    // The server rejects invalid placements, so it's better to find out about them here.
+    validate_placement(
+        &placement,
+        &ruleset.armies[player_id],
+        &ruleset.board,
+        player_id,
+    )
+    .context("The bot made an invalid placement")?;
+
+    write_json(SetupBoardCommandJson::from(placement))?;
 
-    let mut state = State::new_from_json_state(&read_json()?);
+    let mut state = State::new_from_json_state(&read_json()?, ruleset)
+        .context("The server sent an invalid initial game state")?;
 
     loop {
         if state.current_player_id == player_id {
-            write_json(MoveCommandJson::from(bot.get_action(state)))?;
+            let beliefs = Beliefs::new(&state, (player_id + 1) % 2);
+            write_json(MoveCommandJson::from(bot.get_action(state, &beliefs)))?;
         }
 
-        state.update_with_turn(&read_json::<GameStateJson>()?.into());
+        let game_state = match parse_message(read_line()?.as_deref(), player_id)? {
+            ServerMessage::GameState(game_state) => game_state,
+            ServerMessage::GameOver(result) => {
+                bot.on_game_end(result);
+                return Ok(());
+            }
+        };
+
+        let turn = Turn::try_from(game_state)
+            .context("The server sent a game state without a valid turn")?;
+        state
+            .update_with_turn(&turn)
+            .with_context(|| format!("The server sent a turn that can't be played: {turn:?}"))?;
+
+        if let Some(result) = game_result(&state, &turn, player_id) {
+            bot.on_game_end(result);
+            return Ok(());
+        }
+    }
+}
+
+// The result once the turn has ended the game. Only the endings that the bot can see for sure are
+// detected: a captured flag, or the bot being unable to move. Whether the opponent can move depends
+// on ranks the bot doesn't know, so that and the other endings come from the server's game-over
+// message.
+fn game_result(state: &State, turn: &Turn, player_id: usize) -> Option<GameResult> {
+    if captures_flag(turn) {
+        return Some(if turn.player_id == player_id {
+            GameResult::Win
+        } else {
+            GameResult::Loss
+        });
+    }
+
+    let is_stuck = state.current_player_id == player_id && !has_a_possible_move(state);
+    (is_stuck && state.ruleset.end.immobile_player_loses).then_some(GameResult::Loss)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_over_messages_give_the_result() {
+        let result = |line: &str| match parse_message(Some(line), 1).unwrap() {
+            ServerMessage::GameOver(result) => Some(result),
+            ServerMessage::GameState(_) => None,
+        };
+
+        assert_eq!(
+            result(r#"{"GameOver": {"Winner": 1}}"#),
+            Some(GameResult::Win)
+        );
+        assert_eq!(
+            result(r#"{"GameOver": {"Winner": 0}}"#),
+            Some(GameResult::Loss)
+        );
+        assert_eq!(result(r#"{"GameOver": "Draw"}"#), Some(GameResult::Draw));
+
+        let game_state = r#"{"ActivePlayer": 0, "TurnNumber": 3, "Board": [], "LastMove": null}"#;
+        assert_eq!(result(game_state), None);
+
+        // A closed connection and anything unexpected are errors, not endings.
+        for line in [
+            None,
+            Some("game over"),
+            Some("{}"),
+            Some(r#"{"ActivePlayer": 0, "Board": []}"#),
+            Some(r#"{"GameOver": {"Winner": 2}}"#),
+            Some(r#"{"GameOver": "Draw", "Winner": 1}"#),
+        ] {
+            assert!(parse_message(line, 1).is_err(), "{line:?}");
+        }
     }
 }
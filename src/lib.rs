@@ -4,3 +4,7 @@ pub mod game;
 pub mod game_coordinator;
 pub mod json_runner;
 pub mod reservoir_sample;
+pub mod setup_optimizer;
+
+#[cfg(test)]
+mod testing;
@@ -1,11 +1,13 @@
 use strategobot::{
-    bot::{AgressoBot, RandoBot},
+    bot::{AgressoBot, Bot, ConstrainedSetupBot, RandoBot, SetupBookBot},
+    game::Ruleset,
     game_coordinator::{GameCoordinator, Outcome},
     json_runner::run_bot,
 };
 
 use rand::{RngCore, SeedableRng};
 use rand_xoshiro::SplitMix64;
+use std::path::Path;
 use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 fn main() {
@@ -14,8 +16,46 @@ fn main() {
         .expect("Now is later then epoch")
         .as_secs();
     let mut seeder = SplitMix64::seed_from_u64(seed);
-    let bot = Box::new(AgressoBot::new(seeder.next_u64()));
-    run_bot(bot).expect("Communication should work");
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    // `--constraints <file>` deploys placements sampled from a file of setup constraints, one per
+    // line, like "Flag on row 0" or "a Bomb adjacent to the Flag".
+    let constraints = args
+        .iter()
+        .position(|arg| arg == "--constraints")
+        .map(|idx| {
+            let mut option = args.drain(idx..(idx + 2).min(args.len()));
+            option
+                .nth(1)
+                .expect("--constraints should be followed by a file")
+        });
+
+    // The rule variant can be picked with the first argument, the default is barrage.
+    let ruleset = match args.first() {
+        Some(name) => Ruleset::by_name(name).expect("Argument should be a known rule variant"),
+        None => Ruleset::barrage(),
+    };
+
+    let mut bot: Box<dyn Bot> = Box::new(AgressoBot::new(seeder.next_u64()));
+
+    if let Some(path) = constraints {
+        bot = Box::new(
+            ConstrainedSetupBot::from_file(Path::new(&path), &ruleset, bot, seeder.next_u64())
+                .expect("Argument should be a satisfiable constraints file"),
+        );
+    }
+
+    // A setup book can be given as the second argument, and the name of the opponent to keep its
+    // results under as the third. Its setups take precedence over the constraints.
+    if let Some(path) = args.get(1) {
+        let opponent = args.get(2).map_or("server", String::as_str);
+        bot = Box::new(
+            SetupBookBot::from_file(Path::new(path), opponent, bot, seeder.next_u64())
+                .expect("Argument should be a valid setup book"),
+        );
+    }
+
+    run_bot(bot, ruleset).expect("Communication should work");
 }
 
 fn _game_runner_test() {
@@ -31,29 +71,33 @@ fn _game_runner_test() {
         let mut game_coordinator = GameCoordinator::new(
             Box::new(RandoBot::new(seeder.next_u64())),
             Box::new(RandoBot::new(seeder.next_u64())),
+            Ruleset::barrage(),
             5000,
-        );
+        )
+        .expect("Bots should make valid placements");
 
-        let outcome = game_coordinator.play().expect("");
+        let outcome = game_coordinator.play().expect("").outcome;
         outcomes.push(outcome);
     }
 
-    let mut timeouts: usize = 0;
+    let mut draws: usize = 0;
     let mut wins = [0, 0];
     let mut total_turns = 0usize;
 
     for outcome in outcomes.iter() {
         match outcome {
-            Outcome::Win { winner, turn_count } => {
+            Outcome::Win {
+                winner, turn_count, ..
+            } => {
                 wins[*winner] += 1;
                 total_turns += turn_count;
             }
-            Outcome::ReachedMaxTurnCount(_) => timeouts += 1,
+            Outcome::Draw { .. } => draws += 1,
         }
     }
 
     println!(
-        "[Total games: {}] [Timeouts: {timeouts}] [Wins: {} | {}] [Average turns: {}]",
+        "[Total games: {}] [Draws: {draws}] [Wins: {} | {}] [Average turns: {}]",
         outcomes.len(),
         wins[0],
         wins[1],
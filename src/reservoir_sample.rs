@@ -15,13 +15,11 @@ pub fn reservoir_sample<T, I: Iterator<Item = T>, R: Rng>(
         }
     }
 
-    let mut i = k + 1;
-    for sample in source {
+    for (i, sample) in (k + 1..).zip(source) {
         let j = rng.gen_range(0..i);
         if j < k {
             samples[j] = sample;
         }
-        i += 1;
     }
 
     samples
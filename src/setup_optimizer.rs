@@ -0,0 +1,333 @@
+use crate::{
+    bot::{random_placement, Bot, FixedSetupBot, SetupGenerator},
+    game::{Board, Position, Rank, Ruleset, ALL_RANKS, RANK_COUNT},
+    game_coordinator::{GameCoordinator, Outcome},
+};
+use anyhow::Result;
+use rand::{seq::SliceRandom, Rng, RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256StarStar;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+// Creates a fresh bot from a seed, once for every game.
+pub type BotFactory = Box<dyn Fn(u64) -> Box<dyn Bot> + Send + Sync>;
+
+// A deployment for player 0, with the results of the games that were played with it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredSetup {
+    pub placements: Vec<(Rank, Position)>,
+    pub games: usize,
+    pub wins: usize,
+    pub draws: usize,
+}
+
+impl ScoredSetup {
+    pub fn new(placements: Vec<(Rank, Position)>) -> ScoredSetup {
+        ScoredSetup {
+            placements,
+            games: 0,
+            wins: 0,
+            draws: 0,
+        }
+    }
+
+    // Draws count as half a win.
+    pub fn win_rate(&self) -> f32 {
+        if self.games == 0 {
+            return 0.0;
+        }
+
+        (self.wins as f32 + 0.5 * self.draws as f32) / self.games as f32
+    }
+}
+
+// Evolves deployments with a genetic algorithm. Every generation, each setup is played by `player`
+// against every bot of the panel, alternating who moves first. The better half survives with its
+// results, so that the estimates of good setups keep getting more precise, and the other half is
+// replaced by crossovers of the survivors with a few random moves.
+pub struct SetupOptimizer {
+    ruleset: Ruleset,
+    player: BotFactory,
+    panel: Vec<BotFactory>,
+    rng: Xoshiro256StarStar,
+    population_size: usize,
+    games_per_opponent: usize,
+    // The chance that a piece is moved to another square after crossover.
+    mutation_rate: f32,
+    max_turn_count: usize,
+}
+
+impl SetupOptimizer {
+    pub fn new(
+        ruleset: Ruleset,
+        player: BotFactory,
+        panel: Vec<BotFactory>,
+        seed: u64,
+    ) -> SetupOptimizer {
+        SetupOptimizer {
+            ruleset,
+            player,
+            panel,
+            rng: Xoshiro256StarStar::seed_from_u64(seed),
+            population_size: 16,
+            games_per_opponent: 2,
+            mutation_rate: 0.1,
+            max_turn_count: 1000,
+        }
+    }
+
+    pub fn with_population_size(mut self, population_size: usize) -> SetupOptimizer {
+        self.population_size = population_size.max(2);
+        self
+    }
+
+    pub fn with_games_per_opponent(mut self, games_per_opponent: usize) -> SetupOptimizer {
+        self.games_per_opponent = games_per_opponent;
+        self
+    }
+
+    pub fn with_mutation_rate(mut self, mutation_rate: f32) -> SetupOptimizer {
+        self.mutation_rate = mutation_rate;
+        self
+    }
+
+    pub fn with_max_turn_count(mut self, max_turn_count: usize) -> SetupOptimizer {
+        self.max_turn_count = max_turn_count;
+        self
+    }
+
+    // Runs the given number of generations, and returns the last one with the best setup first.
+    pub fn run(&mut self, generations: usize) -> Result<Vec<ScoredSetup>> {
+        let mut population = self.initial_population();
+
+        for generation in 0..generations {
+            if generation > 0 {
+                population = self.next_generation(population);
+            }
+            self.evaluate(&mut population)?;
+        }
+
+        sort_by_win_rate(&mut population);
+        Ok(population)
+    }
+
+    // Half heuristic setups and half random ones, so that the search doesn't start out committed
+    // to the ideas of the heuristic.
+    pub fn initial_population(&mut self) -> Vec<ScoredSetup> {
+        let (army, board) = (&self.ruleset.armies[0], &self.ruleset.board);
+        let generator = SetupGenerator::new();
+
+        (0..self.population_size)
+            .map(|idx| match idx % 2 {
+                0 => generator.generate(&mut self.rng, army, board),
+                _ => random_placement(&mut self.rng, army, board),
+            })
+            .map(ScoredSetup::new)
+            .collect()
+    }
+
+    // Plays the games for every setup, and adds their results.
+    pub fn evaluate(&mut self, population: &mut [ScoredSetup]) -> Result<()> {
+        let seeds = population
+            .iter()
+            .map(|_| self.rng.next_u64())
+            .collect::<Vec<_>>();
+
+        let results = population
+            .par_iter()
+            .zip(seeds)
+            .map(|(setup, seed)| self.play_games(&setup.placements, seed))
+            .collect::<Vec<_>>();
+
+        for (setup, result) in population.iter_mut().zip(results) {
+            let [games, wins, draws] = result?;
+            setup.games += games;
+            setup.wins += wins;
+            setup.draws += draws;
+        }
+
+        Ok(())
+    }
+
+    // Keeps the better half and fills up the rest with their children.
+    pub fn next_generation(&mut self, mut population: Vec<ScoredSetup>) -> Vec<ScoredSetup> {
+        sort_by_win_rate(&mut population);
+        population.truncate(self.population_size.div_ceil(2));
+
+        let survivors = population.len();
+        while population.len() < self.population_size {
+            let a = self.tournament(&population[..survivors]);
+            let b = self.tournament(&population[..survivors]);
+
+            let mut child = crossover(
+                &mut self.rng,
+                &population[a].placements,
+                &population[b].placements,
+                &self.ruleset.board,
+            );
+            mutate(
+                &mut self.rng,
+                &mut child,
+                &self.ruleset.board,
+                self.mutation_rate,
+            );
+
+            population.push(ScoredSetup::new(child));
+        }
+
+        population
+    }
+
+    // The better of two random setups.
+    fn tournament(&mut self, population: &[ScoredSetup]) -> usize {
+        let a = self.rng.gen_range(0..population.len());
+        let b = self.rng.gen_range(0..population.len());
+
+        if population[a].win_rate() >= population[b].win_rate() {
+            a
+        } else {
+            b
+        }
+    }
+
+    // The number of games, wins and draws of the setup against the panel.
+    fn play_games(&self, placements: &[(Rank, Position)], seed: u64) -> Result<[usize; 3]> {
+        let mut seeder = Xoshiro256StarStar::seed_from_u64(seed);
+        let mut results = [0; 3];
+
+        for opponent in &self.panel {
+            for game in 0..self.games_per_opponent {
+                let player = Box::new(FixedSetupBot::new(
+                    placements.to_vec(),
+                    (self.player)(seeder.next_u64()),
+                ));
+                let opponent = opponent(seeder.next_u64());
+
+                let player_id = game % 2;
+                let (p0, p1): (Box<dyn Bot>, Box<dyn Bot>) = match player_id {
+                    0 => (player, opponent),
+                    _ => (opponent, player),
+                };
+
+                let mut game_coordinator =
+                    GameCoordinator::new(p0, p1, self.ruleset, self.max_turn_count)?;
+
+                results[0] += 1;
+                match game_coordinator.play()?.outcome {
+                    Outcome::Win { winner, .. } if winner == player_id => results[1] += 1,
+                    Outcome::Win { .. } => {}
+                    Outcome::Draw { .. } => results[2] += 1,
+                }
+            }
+        }
+
+        Ok(results)
+    }
+}
+
+// The best setup first, and the one with more games when the win rates are equal.
+pub fn sort_by_win_rate(population: &mut [ScoredSetup]) {
+    population.sort_by(|a, b| {
+        b.win_rate()
+            .partial_cmp(&a.win_rate())
+            .unwrap_or(Ordering::Equal)
+            .then(b.games.cmp(&a.games))
+    });
+}
+
+// Takes the columns left of a random cut from `a`, and as much of the rest from `b` as the army
+// allows. The pieces that are left over go to random free squares.
+fn crossover<R: Rng>(
+    rng: &mut R,
+    a: &[(Rank, Position)],
+    b: &[(Rank, Position)],
+    board: &Board,
+) -> Vec<(Rank, Position)> {
+    let cut = rng.gen_range(0..=board.width);
+
+    let mut needed = [0; RANK_COUNT];
+    for (rank, _) in a {
+        needed[*rank as usize] += 1;
+    }
+
+    let mut child = Vec::with_capacity(a.len());
+    let from_a = a.iter().filter(|(_, pos)| pos.x < cut);
+    let from_b = b.iter().filter(|(_, pos)| pos.x >= cut);
+
+    for &(rank, pos) in from_a.chain(from_b) {
+        if needed[rank as usize] > 0 {
+            needed[rank as usize] -= 1;
+            child.push((rank, pos));
+        }
+    }
+
+    let mut free = board
+        .deployment_zone()
+        .filter(|pos| !child.iter().any(|(_, other)| other == pos))
+        .collect::<Vec<_>>();
+    free.shuffle(rng);
+
+    for rank in ALL_RANKS {
+        for _ in 0..needed[rank as usize] {
+            if let Some(pos) = free.pop() {
+                child.push((rank, pos));
+            }
+        }
+    }
+
+    child
+}
+
+// Moves every piece with a chance of `rate` to a random square of the deployment zone, swapping
+// places with the piece that was there.
+fn mutate<R: Rng>(rng: &mut R, placements: &mut [(Rank, Position)], board: &Board, rate: f32) {
+    let squares = board.deployment_zone().collect::<Vec<_>>();
+
+    for idx in 0..placements.len() {
+        if !rng.gen_bool(rate.clamp(0.0, 1.0) as f64) {
+            continue;
+        }
+
+        let Some(&target) = squares.choose(rng) else {
+            return;
+        };
+
+        let pos = placements[idx].1;
+        if let Some(other) = placements.iter_mut().find(|(_, other)| *other == target) {
+            other.1 = pos;
+        }
+        placements[idx].1 = target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::RandoBot;
+    use crate::game::validate_placement;
+
+    #[test]
+    fn test_evolved_setups_are_valid_and_sorted() {
+        let ruleset = Ruleset::duel();
+        let rando = || Box::new(|seed| Box::new(RandoBot::new(seed)) as Box<dyn Bot>) as BotFactory;
+
+        let mut optimizer = SetupOptimizer::new(ruleset, rando(), vec![rando(), rando()], 3)
+            .with_population_size(6)
+            .with_games_per_opponent(2)
+            .with_mutation_rate(0.3)
+            .with_max_turn_count(200);
+
+        let population = optimizer.run(3).unwrap();
+        assert_eq!(population.len(), 6);
+
+        for (setup, next) in population.iter().zip(&population[1..]) {
+            assert!(setup.win_rate() >= next.win_rate());
+        }
+
+        for setup in &population {
+            assert!(setup.games >= 4);
+            validate_placement(&setup.placements, &ruleset.armies[0], &ruleset.board, 0).unwrap();
+        }
+    }
+}
@@ -0,0 +1,106 @@
+// Fixtures that the tests of several modules share.
+
+use crate::bot::{random_placement, AgressoBot, Bot, RandoBot};
+use crate::game::{Ruleset, State};
+use crate::game_coordinator::{GameCoordinator, Outcome, WinReason};
+use rand::Rng;
+
+// The start of a game where both players deployed at random.
+pub fn random_start<R: Rng>(rng: &mut R, ruleset: Ruleset) -> State {
+    let placements = [0, 1].map(|id| {
+        random_placement(rng, &ruleset.armies[id], &ruleset.board)
+            .into_iter()
+            .map(|(rank, pos)| match id {
+                0 => (rank, pos),
+                _ => (rank, pos.reversed(&ruleset.board)),
+            })
+            .collect::<Vec<_>>()
+    });
+
+    State::new_with_placements(ruleset, &[&placements[0], &placements[1]])
+}
+
+// Plays a game to the end and checks that neither bot made an illegal action.
+pub fn play_legal_game(
+    p0: Box<dyn Bot>,
+    p1: Box<dyn Bot>,
+    ruleset: Ruleset,
+    max_turn_count: usize,
+) -> Outcome {
+    let mut game_coordinator = GameCoordinator::new(p0, p1, ruleset, max_turn_count).unwrap();
+
+    let outcome = game_coordinator.play().unwrap().outcome;
+    assert!(
+        !matches!(
+            outcome,
+            Outcome::Win {
+                reason: WinReason::IllegalAction { .. },
+                ..
+            }
+        ),
+        "{outcome:?}"
+    );
+
+    outcome
+}
+
+// The share of the points the bot gets against the opponent, where a draw is worth half a win.
+// The bots swap sides every game, and each game gets its own seeds.
+pub fn match_score(
+    bot: &dyn Fn(u64) -> Box<dyn Bot>,
+    opponent: &dyn Fn(u64) -> Box<dyn Bot>,
+    ruleset: Ruleset,
+    games: u64,
+) -> f32 {
+    let mut points = 0.0;
+
+    for game in 0..games {
+        let player_id = (game % 2) as usize;
+        let (bot, opponent) = (bot(2 * game), opponent(2 * game + 1));
+        let (p0, p1) = match player_id {
+            0 => (bot, opponent),
+            _ => (opponent, bot),
+        };
+
+        points += match play_legal_game(p0, p1, ruleset, 1000) {
+            Outcome::Win { winner, .. } if winner == player_id => 1.0,
+            Outcome::Win { .. } => 0.0,
+            Outcome::Draw { .. } => 0.5,
+        };
+    }
+
+    points / games as f32
+}
+
+// Plays a short barrage game from each side, against RandoBot and AgressoBot, and checks that the
+// bot only makes legal actions.
+pub fn assert_plays_legally(bot: &dyn Fn(u64) -> Box<dyn Bot>) {
+    play_legal_game(bot(1), Box::new(RandoBot::new(2)), Ruleset::barrage(), 300);
+    play_legal_game(
+        Box::new(AgressoBot::new(3)),
+        bot(4),
+        Ruleset::barrage(),
+        300,
+    );
+}
+
+// Checks that the bot scores at least the minimum against AgressoBot and RandoBot, in that order,
+// over the given number of barrage games against each.
+pub fn assert_beats_simple_bots(
+    bot: &dyn Fn(u64) -> Box<dyn Bot>,
+    games: u64,
+    min_scores: [f32; 2],
+) {
+    let opponents: [&dyn Fn(u64) -> Box<dyn Bot>; 2] =
+        [&|seed| Box::new(AgressoBot::new(seed)), &|seed| {
+            Box::new(RandoBot::new(seed))
+        }];
+
+    let scores = opponents.map(|opponent| match_score(bot, opponent, Ruleset::barrage(), games));
+    println!(
+        "{} against AgressoBot, {} against RandoBot",
+        scores[0], scores[1]
+    );
+
+    assert!(scores[0] >= min_scores[0] && scores[1] >= min_scores[1]);
+}